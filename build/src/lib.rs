@@ -1,11 +1,54 @@
 use std::fs::File;
 use std::io::Write;
 
+/// The backend to generate code for.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Codec {
+    #[cfg(feature = "protobuf-codec")]
+    Protobuf,
+    #[cfg(feature = "protobuf3-codec")]
+    Protobuf3,
+    #[cfg(feature = "prost-codec")]
+    Prost,
+    #[cfg(feature = "quick-protobuf-codec")]
+    QuickProtobuf,
+}
+
+impl Codec {
+    /// Gets the only enabled codec. Features are unified across the crates in
+    /// a workspace, so guessing among several would make the generated code
+    /// depend on which other crates are built.
+    fn from_features() -> Codec {
+        #[allow(unused_mut)]
+        let mut codecs = Vec::new();
+        #[cfg(feature = "prost-codec")]
+        codecs.push(Codec::Prost);
+        #[cfg(feature = "protobuf-codec")]
+        codecs.push(Codec::Protobuf);
+        #[cfg(feature = "protobuf3-codec")]
+        codecs.push(Codec::Protobuf3);
+        #[cfg(feature = "quick-protobuf-codec")]
+        codecs.push(Codec::QuickProtobuf);
+        match codecs.as_slice() {
+            [codec] => *codec,
+            [] => panic!("no codec feature is enabled"),
+            _ => panic!(
+                "codec features {:?} are all enabled, choose one with `Builder::codec`",
+                codecs
+            ),
+        }
+    }
+}
+
 #[derive(Default)]
 pub struct Builder {
     out_dir: Option<String>,
     includes: Vec<String>,
     sources: Vec<String>,
+    unknown_enum_as_default: bool,
+    jinkela_path: Option<String>,
+    owned: bool,
+    codec: Option<Codec>,
 }
 
 impl Builder {
@@ -24,6 +67,36 @@ impl Builder {
         self
     }
 
+    /// Make getters of enumeration fields return the default value instead of
+    /// panicking on values unknown to the enum. It only affects prost-codec.
+    pub fn unknown_enum_as_default(&mut self, enable: bool) -> &mut Builder {
+        self.unknown_enum_as_default = enable;
+        self
+    }
+
+    /// Set the path to jinkela used by generated code, like `::my_jinkela` when
+    /// it's renamed in Cargo.toml.
+    pub fn jinkela_path(&mut self, path: impl Into<String>) -> &mut Builder {
+        self.jinkela_path = Some(path.into());
+        self
+    }
+
+    /// Generate messages that own their fields instead of borrowing from the
+    /// input. `GenericMessage` is only implemented for messages without
+    /// borrowed fields. It only affects quick-protobuf-codec.
+    pub fn owned(&mut self, enable: bool) -> &mut Builder {
+        self.owned = enable;
+        self
+    }
+
+    /// Set the backend to generate code for. More than one codec feature can
+    /// be enabled when crates in the same workspace choose different backends,
+    /// in which case `build` panics unless it's set.
+    pub fn codec(&mut self, codec: Codec) -> &mut Builder {
+        self.codec = Some(codec);
+        self
+    }
+
     pub fn build(&self) {
         for (key, value) in std::env::vars() {
             println!("{}: {}", key, value);
@@ -55,6 +128,11 @@ impl Builder {
         }).unwrap();
 
         self.internal_build(&proto_dir, &desc_file);
+        let mod_file = format!("{}/mod.rs", proto_dir);
+        if std::path::Path::new(&mod_file).exists() {
+            // pb-rs and rust-protobuf 3 write their own module trees.
+            return;
+        }
         let modules: Vec<_> = std::fs::read_dir(&proto_dir).unwrap().filter_map(|res| {
             let path = match res {
                 Ok(e) => e.path(),
@@ -67,7 +145,7 @@ impl Builder {
                 None
             }
         }).collect();
-        let mut f = File::create(format!("{}/mod.rs", proto_dir)).unwrap();
+        let mut f = File::create(&mod_file).unwrap();
         for (module, file_name) in &modules {
             if !module.contains('.') {
                 writeln!(f, "pub mod {};", module).unwrap();
@@ -85,8 +163,21 @@ impl Builder {
         }
     }
 
-    #[cfg(feature = "protobuf-codec")]
     fn internal_build(&self, out_dir: &str, desc_file: &str) {
+        match self.codec.unwrap_or_else(Codec::from_features) {
+            #[cfg(feature = "protobuf-codec")]
+            Codec::Protobuf => self.build_protobuf(out_dir, desc_file),
+            #[cfg(feature = "protobuf3-codec")]
+            Codec::Protobuf3 => self.build_protobuf3(out_dir, desc_file),
+            #[cfg(feature = "prost-codec")]
+            Codec::Prost => self.build_prost(out_dir, desc_file),
+            #[cfg(feature = "quick-protobuf-codec")]
+            Codec::QuickProtobuf => self.build_quick_protobuf(out_dir, desc_file),
+        }
+    }
+
+    #[cfg(feature = "protobuf-codec")]
+    fn build_protobuf(&self, out_dir: &str, desc_file: &str) {
         println!("building protobuf at {} for {}", out_dir, desc_file);
         
         let desc_bytes = std::fs::read(&desc_file).unwrap();
@@ -110,21 +201,86 @@ impl Builder {
             &std::path::Path::new(out_dir),
             &protobuf_codegen::Customize::default(),
         ).unwrap();
-        self.build_grpcio(&desc.get_file(), &files_to_generate, &out_dir);
+        let jinkela = self.jinkela_path.as_ref().map_or("::jinkela", |p| p.as_str());
+        for file in desc.get_file() {
+            if files_to_generate.iter().any(|f| f == file.get_name()) {
+                let path = std::path::Path::new(out_dir).join(protobuf_codegen::proto_name_to_rs(file.get_name()));
+                impl_protobuf(&path, jinkela, file);
+            }
+        }
+        self.build_protobuf_grpcio(&desc.get_file(), &files_to_generate, &out_dir);
+    }
+
+    /// rust-protobuf 3 only generates code through its own parser, which
+    /// reads the proto files again.
+    #[cfg(feature = "protobuf3-codec")]
+    fn build_protobuf3(&self, out_dir: &str, desc_file: &str) {
+        println!("building protobuf3 at {} for {}", out_dir, desc_file);
+        let jinkela = self.jinkela_path.as_ref().map_or("::jinkela", |p| p.as_str());
+        let mut attrs = format!("#[derive({}::Protobuf3)]", jinkela);
+        if let Some(ref path) = self.jinkela_path {
+            attrs.push_str(&format!("\n#[jinkela(crate = \"{}\")]", path));
+        }
+        protobuf3_codegen::Codegen::new()
+            .pure()
+            .out_dir(out_dir)
+            .includes(&self.includes)
+            .inputs(&self.sources)
+            .customize_callback(Protobuf3Derive { attrs })
+            .run()
+            .unwrap();
     }
 
     #[cfg(feature = "prost-codec")]
-    fn internal_build(&self, out_dir: &str, desc_file: &str) {
+    fn build_prost(&self, out_dir: &str, desc_file: &str) {
+        use prost::Message;
+
         println!("building prost at {}", out_dir);
         let mut cfg = prost_build::Config::new();
-        cfg.type_attribute(".", "#[derive(::jinkela::Classicalize)]").out_dir(out_dir);
+        let jinkela = self.jinkela_path.as_ref().map_or("::jinkela", |p| p.as_str());
+        cfg.type_attribute(".", format!("#[derive({}::Classicalize)]", jinkela)).out_dir(out_dir);
+        if let Some(ref path) = self.jinkela_path {
+            cfg.type_attribute(".", format!("#[jinkela(crate = \"{}\")]", path));
+        }
+        if self.unknown_enum_as_default {
+            cfg.type_attribute(".", "#[jinkela(unknown_enum = \"default\")]");
+        }
+        // PROST! renames enum values, so the names in proto files are kept for `GenericEnum`.
+        let desc_bytes = std::fs::read(&desc_file).unwrap();
+        let desc = prost_types::FileDescriptorSet::decode(&desc_bytes).unwrap();
+        for (path, name) in enum_value_names(&desc) {
+            cfg.field_attribute(path, format!("#[jinkela(name = \"{}\")]", name));
+        }
         cfg.compile_protos(&self.sources, &self.includes).unwrap();
 
-        self.build_grpcio(out_dir, desc_file);
+        self.build_prost_grpcio(out_dir, desc_file);
+    }
+
+    /// pb-rs can't read descriptor sets, so it parses the proto files itself
+    /// and the descriptor set tells which types it generates.
+    #[cfg(feature = "quick-protobuf-codec")]
+    fn build_quick_protobuf(&self, out_dir: &str, desc_file: &str) {
+        use prost::Message;
+
+        println!("building quick-protobuf at {} for {}", out_dir, desc_file);
+        let out_dir = std::path::Path::new(out_dir).to_owned();
+        let inputs: Vec<_> = self.sources.iter().map(std::path::PathBuf::from).collect();
+        let includes: Vec<_> = self.includes.iter().map(std::path::PathBuf::from).collect();
+        let configs = pb_rs::ConfigBuilder::new(&inputs, None, Some(&out_dir), &includes)
+            .unwrap()
+            .dont_use_cow(self.owned)
+            .build();
+        pb_rs::types::FileDescriptor::run(&configs).unwrap();
+
+        let desc_bytes = std::fs::read(&desc_file).unwrap();
+        let desc = prost_types::FileDescriptorSet::decode(&desc_bytes).unwrap();
+        let types = quick_protobuf_types(&desc, self.owned);
+        let jinkela = self.jinkela_path.as_ref().map_or("::jinkela", |p| p.as_str());
+        impl_quick_protobuf(&out_dir, &out_dir, jinkela, &types);
     }
 
     #[cfg(feature = "grpcio-protobuf-codec")]
-    fn build_grpcio(&self, desc: &[protobuf::descriptor::FileDescriptorProto], files_to_generates: &[String], output: &str) {
+    fn build_protobuf_grpcio(&self, desc: &[protobuf::descriptor::FileDescriptorProto], files_to_generates: &[String], output: &str) {
         println!("building protobuf with grpcio at {}", output);
         let output_dir = std::path::Path::new(output);
         let results = grpcio_compiler::codegen::gen(&desc, &files_to_generates);
@@ -136,10 +292,10 @@ impl Builder {
     }
 
     #[cfg(all(feature = "protobuf-codec", not(feature = "grpcio-protobuf-codec")))]
-    fn build_grpcio(&self, _: &[protobuf::descriptor::FileDescriptorProto], _: &[String], _: &str) {}
+    fn build_protobuf_grpcio(&self, _: &[protobuf::descriptor::FileDescriptorProto], _: &[String], _: &str) {}
 
     #[cfg(feature = "grpcio-prost-codec")]
-    fn build_grpcio(&self, out_dir: &str, desc_file: &str) {
+    fn build_prost_grpcio(&self, out_dir: &str, desc_file: &str) {
         use prost::Message;
         
         let desc_bytes = std::fs::read(&desc_file).unwrap();
@@ -166,5 +322,428 @@ impl Builder {
     }
 
     #[cfg(all(feature = "prost-codec", not(feature = "grpcio-prost-codec")))]
-    fn build_grpcio(&self, _out_dir: &str, _desc_file: &str) {}
+    fn build_prost_grpcio(&self, _out_dir: &str, _desc_file: &str) {}
+}
+
+/// Gets the fully qualified paths of all enum values and their names.
+#[cfg(feature = "prost-codec")]
+fn enum_value_names(desc: &prost_types::FileDescriptorSet) -> Vec<(String, String)> {
+    fn collect(
+        scope: &str,
+        enums: &[prost_types::EnumDescriptorProto],
+        messages: &[prost_types::DescriptorProto],
+        names: &mut Vec<(String, String)>,
+    ) {
+        for e in enums {
+            for v in &e.value {
+                names.push((format!("{}.{}.{}", scope, e.name(), v.name()), v.name().to_owned()));
+            }
+        }
+        for m in messages {
+            let scope = format!("{}.{}", scope, m.name());
+            collect(&scope, &m.enum_type, &m.nested_type, names);
+        }
+    }
+
+    let mut names = Vec::new();
+    for file in &desc.file {
+        let scope = match file.package() {
+            "" => String::new(),
+            package => format!(".{}", package),
+        };
+        collect(&scope, &file.enum_type, &file.message_type, &mut names);
+    }
+    names
+}
+
+/// Gets the messages and enums rust-protobuf generates for a proto file.
+/// Nested types are named like `Outer_Inner` and map entries are not generated.
+#[cfg(feature = "protobuf-codec")]
+fn protobuf_types(file: &protobuf::descriptor::FileDescriptorProto) -> (Vec<String>, Vec<String>) {
+    use protobuf::descriptor::{DescriptorProto, EnumDescriptorProto};
+
+    fn collect(
+        prefix: &str,
+        messages: &[DescriptorProto],
+        enums: &[EnumDescriptorProto],
+        types: &mut (Vec<String>, Vec<String>),
+    ) {
+        for e in enums {
+            types.1.push(format!("{}{}", prefix, e.get_name()));
+        }
+        for m in messages {
+            if m.get_options().get_map_entry() {
+                continue;
+            }
+            let name = format!("{}{}", prefix, m.get_name());
+            collect(&format!("{}_", name), m.get_nested_type(), m.get_enum_type(), types);
+            types.0.push(name);
+        }
+    }
+
+    let mut types = (Vec::new(), Vec::new());
+    collect("", file.get_message_type(), file.get_enum_type(), &mut types);
+    types
+}
+
+/// Appends implementations of `GenericMessage` and `GenericEnum` to the file
+/// generated by rust-protobuf for the proto file.
+#[cfg(feature = "protobuf-codec")]
+fn impl_protobuf(path: &std::path::Path, jinkela: &str, file: &protobuf::descriptor::FileDescriptorProto) {
+    let (messages, enums) = protobuf_types(file);
+    let mut f = std::fs::OpenOptions::new().append(true).open(path).unwrap();
+    if !messages.is_empty() {
+        writeln!(f, "\n{}::protobuf_message!({});", jinkela, messages.join(", ")).unwrap();
+    }
+    if !enums.is_empty() {
+        writeln!(f, "{}::protobuf_enum!({});", jinkela, enums.join(", ")).unwrap();
+    }
+}
+
+/// Derives `Protobuf3` on messages and enums generated by rust-protobuf 3.
+#[cfg(feature = "protobuf3-codec")]
+struct Protobuf3Derive {
+    attrs: String,
+}
+
+#[cfg(feature = "protobuf3-codec")]
+impl protobuf3_codegen::CustomizeCallback for Protobuf3Derive {
+    fn message(&self, _: &protobuf3::reflect::MessageDescriptor) -> protobuf3_codegen::Customize {
+        protobuf3_codegen::Customize::default().before(&self.attrs)
+    }
+
+    fn enumeration(&self, _: &protobuf3::reflect::EnumDescriptor) -> protobuf3_codegen::Customize {
+        protobuf3_codegen::Customize::default().before(&self.attrs)
+    }
+}
+
+/// Messages that own their fields and enums generated by pb-rs for a proto
+/// file, with paths relative to the generated file. Messages are rendered as
+/// the input of `quick_protobuf_message!`.
+#[cfg(feature = "quick-protobuf-codec")]
+#[derive(Debug, Default, PartialEq)]
+struct QuickProtobufTypes {
+    messages: Vec<String>,
+    enums: Vec<(String, Vec<String>)>,
+}
+
+/// Collects the types pb-rs generates for every file in the descriptor set.
+/// Nested types are put in `mod_<Parent>` modules, map entries are not
+/// generated and oneofs hold values, so they are all skipped.
+#[cfg(feature = "quick-protobuf-codec")]
+fn quick_protobuf_types(
+    desc: &prost_types::FileDescriptorSet,
+    owned: bool,
+) -> Vec<(&prost_types::FileDescriptorProto, QuickProtobufTypes)> {
+    use prost_types::field_descriptor_proto::{Label, Type};
+    use prost_types::{DescriptorProto, EnumDescriptorProto};
+    use std::collections::{HashMap, HashSet};
+
+    fn walk<'a>(
+        scope: &str,
+        messages: &'a [DescriptorProto],
+        proto3: bool,
+        all: &mut HashMap<String, (&'a DescriptorProto, bool)>,
+    ) {
+        for m in messages {
+            let name = format!("{}.{}", scope, m.name());
+            walk(&name, &m.nested_type, proto3, all);
+            all.insert(name, (m, proto3));
+        }
+    }
+
+    // pb-rs borrows strings, bytes and packed fixed size numbers from the input
+    // unless asked not to, and so do the messages that contain them.
+    let mut all = HashMap::new();
+    for file in &desc.file {
+        let scope = match file.package() {
+            "" => String::new(),
+            package => format!(".{}", package),
+        };
+        walk(&scope, &file.message_type, file.syntax() == "proto3", &mut all);
+    }
+    let mut borrowed = HashSet::new();
+    let mut changed = !owned;
+    while changed {
+        changed = false;
+        for (name, (m, proto3)) in &all {
+            if borrowed.contains(name) {
+                continue;
+            }
+            let borrows = m.field.iter().any(|f| match f.r#type() {
+                Type::String | Type::Bytes => true,
+                Type::Fixed32 | Type::Sfixed32 | Type::Float | Type::Fixed64 | Type::Sfixed64 | Type::Double => {
+                    let packed = f.options.as_ref().and_then(|o| o.packed).unwrap_or(*proto3);
+                    f.label() == Label::Repeated && packed
+                }
+                Type::Message => borrowed.contains(f.type_name()),
+                _ => false,
+            });
+            if borrows {
+                borrowed.insert(name.clone());
+                changed = true;
+            }
+        }
+    }
+
+    /// Renders the fields `merge_from` has to fold of every message reachable
+    /// from name through them, like `".a.B" { 1 => ".a.C", 2 in 0 }`.
+    fn merge_fields(name: &str, all: &HashMap<String, (&DescriptorProto, bool)>) -> String {
+        let mut names = vec![name.to_owned()];
+        let mut entries = Vec::new();
+        let mut i = 0;
+        while i < names.len() {
+            let m = all[&names[i]].0;
+            let mut fields = Vec::new();
+            for f in &m.field {
+                let message = f.r#type() == Type::Message && f.label() != Label::Repeated;
+                if !message && f.oneof_index.is_none() {
+                    continue;
+                }
+                let mut field = f.number().to_string();
+                if let Some(oneof) = f.oneof_index {
+                    field.push_str(&format!(" in {}", oneof));
+                }
+                if message {
+                    field.push_str(&format!(" => \"{}\"", f.type_name()));
+                    if !names.iter().any(|n| n == f.type_name()) {
+                        names.push(f.type_name().to_owned());
+                    }
+                }
+                fields.push(field);
+            }
+            if !fields.is_empty() {
+                entries.push(format!("\"{}\" {{ {} }}", names[i], fields.join(", ")));
+            } else if i == 0 {
+                // Nothing to fold at all.
+                break;
+            }
+            i += 1;
+        }
+        entries.join(", ")
+    }
+
+    fn collect(
+        scope: &str,
+        module: &str,
+        messages: &[DescriptorProto],
+        enums: &[EnumDescriptorProto],
+        borrowed: &HashSet<String>,
+        all: &HashMap<String, (&DescriptorProto, bool)>,
+        types: &mut QuickProtobufTypes,
+    ) {
+        for e in enums {
+            let variants = e.value.iter().map(|v| v.name().to_owned()).collect();
+            types.enums.push((format!("{}{}", module, e.name()), variants));
+        }
+        for m in messages {
+            if m.options.as_ref().is_some_and(|o| o.map_entry()) {
+                continue;
+            }
+            let name = format!("{}.{}", scope, m.name());
+            if !borrowed.contains(&name) {
+                let fields = merge_fields(&name, all);
+                let braces = if fields.is_empty() { "{}".to_owned() } else { format!("{{ {} }}", fields) };
+                types.messages.push(format!("{}{} {}", module, m.name(), braces));
+            }
+            let module = format!("{}mod_{}::", module, m.name());
+            collect(&name, &module, &m.nested_type, &m.enum_type, borrowed, all, types);
+        }
+    }
+
+    desc.file
+        .iter()
+        .map(|file| {
+            let scope = match file.package() {
+                "" => String::new(),
+                package => format!(".{}", package),
+            };
+            let mut types = QuickProtobufTypes::default();
+            collect(&scope, "", &file.message_type, &file.enum_type, &borrowed, &all, &mut types);
+            (file, types)
+        })
+        .collect()
+}
+
+/// Appends implementations of `GenericMessage` and `GenericEnum` to every file
+/// generated by pb-rs under dir. A generated file is named after its proto
+/// file and put in the directory of the package.
+#[cfg(feature = "quick-protobuf-codec")]
+fn impl_quick_protobuf(
+    root: &std::path::Path,
+    dir: &std::path::Path,
+    jinkela: &str,
+    types: &[(&prost_types::FileDescriptorProto, QuickProtobufTypes)],
+) {
+    for entry in std::fs::read_dir(dir).unwrap() {
+        let path = entry.unwrap().path();
+        if path.is_dir() {
+            impl_quick_protobuf(root, &path, jinkela, types);
+            continue;
+        }
+        if path.extension() != Some(std::ffi::OsStr::new("rs")) || path.ends_with("mod.rs") {
+            continue;
+        }
+        let stem = path.file_stem().unwrap().to_string_lossy().into_owned();
+        let package: Vec<_> = path
+            .parent()
+            .unwrap()
+            .strip_prefix(root)
+            .unwrap()
+            .iter()
+            .map(|c| c.to_string_lossy().into_owned())
+            .collect();
+        let found = types.iter().find(|(file, _)| {
+            let name = std::path::Path::new(file.name());
+            let file_stem = name.file_stem().unwrap().to_string_lossy().replace('-', "_");
+            let file_package: Vec<_> = file.package().split('.').filter(|p| !p.is_empty()).collect();
+            file_stem == stem && (package.is_empty() || package == file_package)
+        });
+        let types = match found {
+            Some((_, types)) => types,
+            None => continue,
+        };
+        let mut f = std::fs::OpenOptions::new().append(true).open(&path).unwrap();
+        if !types.messages.is_empty() {
+            writeln!(f, "\n{}::quick_protobuf_message!({});", jinkela, types.messages.join(", ")).unwrap();
+        }
+        for (name, variants) in &types.enums {
+            writeln!(f, "{}::quick_protobuf_enum!({} {{ {} }});", jinkela, name, variants.join(", ")).unwrap();
+        }
+    }
+}
+
+#[cfg(all(test, feature = "quick-protobuf-codec"))]
+mod tests {
+    use super::*;
+    use prost_types::field_descriptor_proto::{Label, Type};
+    use prost_types::{
+        DescriptorProto, EnumDescriptorProto, EnumValueDescriptorProto, FieldDescriptorProto, FileDescriptorProto,
+        FileDescriptorSet, MessageOptions,
+    };
+
+    fn field(name: &str, label: Label, ty: Type, type_name: &str) -> FieldDescriptorProto {
+        FieldDescriptorProto {
+            name: Some(name.to_owned()),
+            number: Some(1),
+            label: Some(label as i32),
+            r#type: Some(ty as i32),
+            type_name: if type_name.is_empty() { None } else { Some(type_name.to_owned()) },
+            ..Default::default()
+        }
+    }
+
+    fn message(name: &str, field: Vec<FieldDescriptorProto>) -> DescriptorProto {
+        DescriptorProto { name: Some(name.to_owned()), field, ..Default::default() }
+    }
+
+    fn enumeration(name: &str, values: &[&str]) -> EnumDescriptorProto {
+        let value = values
+            .iter()
+            .enumerate()
+            .map(|(i, v)| EnumValueDescriptorProto { name: Some(v.to_string()), number: Some(i as i32), ..Default::default() })
+            .collect();
+        EnumDescriptorProto { name: Some(name.to_owned()), value, ..Default::default() }
+    }
+
+    #[test]
+    fn test_quick_protobuf_types() {
+        let mut entry = message(
+            "TagsEntry",
+            vec![field("key", Label::Optional, Type::String, ""), field("value", Label::Optional, Type::Int32, "")],
+        );
+        entry.options = Some(MessageOptions { map_entry: Some(true), ..Default::default() });
+        let mut polygon = message(
+            "Polygon",
+            vec![
+                field("points", Label::Repeated, Type::Message, ".geo.Point"),
+                field("tags", Label::Repeated, Type::Message, ".geo.Polygon.TagsEntry"),
+            ],
+        );
+        polygon.nested_type = vec![entry, message("Edge", vec![field("len", Label::Optional, Type::Double, "")])];
+        polygon.enum_type = vec![enumeration("Kind", &["KIND_UNKNOWN", "KIND_CONVEX"])];
+        let mut end = field("end", Label::Optional, Type::Message, ".geo.Point");
+        end.number = Some(2);
+        end.oneof_index = Some(0);
+        let mut len = field("len", Label::Optional, Type::Double, "");
+        len.number = Some(3);
+        len.oneof_index = Some(0);
+        let segment = message("Segment", vec![field("start", Label::Optional, Type::Message, ".geo.Point"), end, len]);
+        let file = FileDescriptorProto {
+            name: Some("geo/shape.proto".to_owned()),
+            package: Some("geo".to_owned()),
+            syntax: Some("proto3".to_owned()),
+            message_type: vec![
+                message("Point", vec![field("x", Label::Optional, Type::Int64, "")]),
+                message("Label", vec![field("text", Label::Optional, Type::String, "")]),
+                polygon,
+                message("Samples", vec![field("values", Label::Repeated, Type::Double, "")]),
+                segment,
+            ],
+            enum_type: vec![enumeration("Shape", &["UNKNOWN", "SQUARE"])],
+            ..Default::default()
+        };
+        let desc = FileDescriptorSet { file: vec![file] };
+
+        let types = quick_protobuf_types(&desc, false);
+        assert_eq!(types.len(), 1);
+        assert_eq!(types[0].0.name(), "geo/shape.proto");
+        assert_eq!(
+            types[0].1,
+            QuickProtobufTypes {
+                messages: vec![
+                    "Point {}".to_owned(),
+                    "mod_Polygon::Edge {}".to_owned(),
+                    r#"Segment { ".geo.Segment" { 1 => ".geo.Point", 2 in 0 => ".geo.Point", 3 in 0 } }"#.to_owned(),
+                ],
+                enums: vec![
+                    ("Shape".to_owned(), vec!["UNKNOWN".to_owned(), "SQUARE".to_owned()]),
+                    ("mod_Polygon::Kind".to_owned(), vec!["KIND_UNKNOWN".to_owned(), "KIND_CONVEX".to_owned()]),
+                ],
+            }
+        );
+
+        let types = quick_protobuf_types(&desc, true);
+        let names: Vec<_> = types[0].1.messages.iter().map(|m| m.split(' ').next().unwrap()).collect();
+        assert_eq!(names, ["Point", "Label", "Polygon", "mod_Polygon::Edge", "Samples", "Segment"]);
+    }
+}
+
+#[cfg(all(test, feature = "protobuf-codec"))]
+mod protobuf_tests {
+    use super::*;
+    use protobuf::descriptor::{DescriptorProto, EnumDescriptorProto, FileDescriptorProto};
+
+    fn message(name: &str) -> DescriptorProto {
+        let mut m = DescriptorProto::new();
+        m.set_name(name.to_owned());
+        m
+    }
+
+    fn enumeration(name: &str) -> EnumDescriptorProto {
+        let mut e = EnumDescriptorProto::new();
+        e.set_name(name.to_owned());
+        e
+    }
+
+    #[test]
+    fn test_protobuf_types() {
+        let mut entry = message("TagsEntry");
+        entry.mut_options().set_map_entry(true);
+        let mut edge = message("Edge");
+        edge.mut_enum_type().push(enumeration("Direction"));
+        let mut polygon = message("Polygon");
+        polygon.mut_nested_type().push(entry);
+        polygon.mut_nested_type().push(edge);
+        polygon.mut_enum_type().push(enumeration("Kind"));
+        let mut file = FileDescriptorProto::new();
+        file.set_name("geo/shape.proto".to_owned());
+        file.mut_message_type().push(message("Point"));
+        file.mut_message_type().push(polygon);
+        file.mut_enum_type().push(enumeration("Shape"));
+
+        let (messages, enums) = protobuf_types(&file);
+        assert_eq!(messages, ["Point", "Polygon_Edge", "Polygon"]);
+        assert_eq!(enums, ["Shape", "Polygon_Kind", "Polygon_Edge_Direction"]);
+    }
 }
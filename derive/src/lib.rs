@@ -3,109 +3,272 @@
 extern crate proc_macro;
 
 use quote::quote;
+use syn::parse_quote;
 use proc_macro::TokenStream;
 use proc_macro2::Span;
 use syn::{
     Data, DataEnum, DataStruct, DeriveInput, Fields, FieldsNamed, PathArguments, GenericArgument,
-    FieldsUnnamed, Ident, Meta, Field, MetaList, NestedMeta, Type, Lit, Path,
+    FieldsUnnamed, Ident, Meta, Field, MetaList, NestedMeta, Type, Lit, Path, Variant, Attribute,
+    Error, Result, Member, Index, Generics,
 };
 
-#[proc_macro_derive(Classicalize, attributes(prost))]
+#[proc_macro_derive(Classicalize, attributes(prost, jinkela))]
 pub fn classicalize(input: TokenStream) -> TokenStream {
     let input: DeriveInput = syn::parse(input).unwrap();
-    let s = match input.data {
-        Data::Struct(s) => classicalize_struct(input.ident, s),
-        Data::Enum(e) => classicalize_enum(input.ident, e),
-        Data::Union(_) => panic!("union is not supported yet."),
-    };
-    s.into()
+    let res = Options::from_attrs(&input.attrs).and_then(|opts| match input.data {
+        Data::Struct(s) => classicalize_struct(input.ident, input.generics, s, opts),
+        Data::Enum(e) => classicalize_enum(input.ident, e, opts),
+        Data::Union(u) => Err(Error::new_spanned(u.union_token, "union is not supported yet")),
+    });
+    match res {
+        Ok(s) => s.into(),
+        Err(e) => e.to_compile_error().into(),
+    }
 }
 
-fn classicalize_optional_message_field(field: &Field) -> proc_macro2::TokenStream {
-    let ident = field.ident.as_ref().unwrap();
-    let mut ident_str = ident.to_string();
-    if ident_str.starts_with("r#") {
-        ident_str = ident_str[2..].to_owned();
+/// Implements `GenericMessage` for messages or `GenericEnum` for enums
+/// generated by rust-protobuf 3. jinkela-build adds it to generated code.
+#[proc_macro_derive(Protobuf3, attributes(jinkela))]
+pub fn protobuf3(input: TokenStream) -> TokenStream {
+    let input: DeriveInput = syn::parse(input).unwrap();
+    let res = Options::from_attrs(&input.attrs).and_then(|opts| {
+        let ident = &input.ident;
+        let invocation = match input.data {
+            Data::Struct(_) => quote!(_jinkela::protobuf3_message!(#ident);),
+            Data::Enum(ref e) if e.variants.iter().all(|v| v.fields == Fields::Unit) => {
+                quote!(_jinkela::protobuf3_enum!(#ident);)
+            }
+            Data::Enum(ref e) => return Err(Error::new_spanned(e.enum_token, "oneof is not supported")),
+            Data::Union(ref u) => return Err(Error::new_spanned(u.union_token, "union is not supported")),
+        };
+        let dummy_const = Ident::new(&format!("{}_PROTOBUF3", ident), Span::call_site());
+        let import = opts.import_jinkela();
+        Ok(quote! {
+            #[allow(non_snake_case, unused_attributes)]
+            const #dummy_const: () = {
+                #import
+
+                #invocation
+            };
+        })
+    });
+    match res {
+        Ok(s) => s.into(),
+        Err(e) => e.to_compile_error().into(),
     }
-    let ty = &field.ty;
-    let ty = match ty {
-        Type::Path(tp) => {
-            let wrapper = tp.path.segments.iter().last().unwrap();
-            assert_eq!(wrapper.ident, "Option", "expected option, but got {:?}", ty);
-            let generic_arg = match wrapper.arguments {
-                PathArguments::AngleBracketed(ref params) => params.args.iter().next().unwrap(),
-                _ => panic!("unexpected token {:?}", ty),
+}
+
+/// Options set by `#[jinkela(...)]` on the message.
+#[derive(Default)]
+struct Options {
+    /// Whether getters of enumeration fields return the default value instead
+    /// of panicking on unknown values, set by `unknown_enum = "default"`.
+    unknown_enum_as_default: bool,
+    /// The path to jinkela, set by `crate = "path"` when it's renamed or
+    /// re-exported.
+    krate: Option<Path>,
+}
+
+impl Options {
+    fn from_attrs(attrs: &[Attribute]) -> Result<Options> {
+        let mut opts = Options::default();
+        for a in attrs {
+            let nested = match a.interpret_meta() {
+                Some(Meta::List(MetaList { ident, nested, .. })) if ident == "jinkela" => nested,
+                _ => continue,
             };
-            match generic_arg {
-                GenericArgument::Type(ty) => ty,
-                _ => panic!("expected generic, but get {:?}", ty),
+            for n in nested {
+                match n {
+                    NestedMeta::Meta(Meta::NameValue(ref nv)) if nv.ident == "unknown_enum" => {
+                        opts.unknown_enum_as_default = match nv.lit {
+                            Lit::Str(ref s) if s.value() == "default" => true,
+                            Lit::Str(ref s) if s.value() == "panic" => false,
+                            _ => return Err(Error::new_spanned(&nv.lit, "expected \"default\" or \"panic\"")),
+                        };
+                    }
+                    NestedMeta::Meta(Meta::NameValue(ref nv)) if nv.ident == "crate" => {
+                        opts.krate = Some(parse_type_path(&nv.lit, "crate path")?);
+                    }
+                    _ => return Err(Error::new_spanned(n, "unknown jinkela attribute")),
+                }
             }
-        },
-        _ => panic!("unexpected type {:?}", ty),
-    };
+        }
+        Ok(opts)
+    }
+
+    /// Imports jinkela as `_jinkela`. Everything else generated code needs is
+    /// reached through `_jinkela::__private`, so users only depend on jinkela.
+    fn import_jinkela(&self) -> proc_macro2::TokenStream {
+        match self.krate {
+            Some(ref path) => quote!(use #path as _jinkela;),
+            None => quote!(extern crate jinkela as _jinkela;),
+        }
+    }
+}
+
+/// Gets the member to access the field and the name used in accessors.
+///
+/// Fields of tuple structs are named by their positions, like `get_0`.
+fn field_name(field: &Field, index: usize) -> (Member, String) {
+    match field.ident {
+        Some(ref ident) => {
+            let mut name = ident.to_string();
+            if name.starts_with("r#") {
+                name = name[2..].to_owned();
+            }
+            (Member::Named(ident.clone()), name)
+        }
+        None => (Member::Unnamed(Index::from(index)), index.to_string()),
+    }
+}
+
+/// Gets `T` from a type like `Option<T>`, `what` describes the field in errors.
+fn wrapped_type<'a>(ty: &'a Type, wrapper: &str, what: &str) -> Result<&'a Type> {
+    if let Type::Path(tp) = ty {
+        if let Some(seg) = tp.path.segments.iter().last() {
+            if seg.ident == wrapper {
+                if let PathArguments::AngleBracketed(ref params) = seg.arguments {
+                    if let Some(GenericArgument::Type(ty)) = params.args.iter().next() {
+                        return Ok(ty);
+                    }
+                }
+            }
+        }
+    }
+    Err(Error::new_spanned(ty, format!("expected `{}<T>` for {}", wrapper, what)))
+}
+
+/// Parses the type in an attribute like `enumeration = "E"`.
+fn parse_type_path(lit: &Lit, what: &str) -> Result<Path> {
+    match lit {
+        Lit::Str(s) => s.parse().map_err(|e| Error::new_spanned(s, format!("invalid {}: {}", what, e))),
+        _ => Err(Error::new_spanned(lit, format!("expected {} as a string", what))),
+    }
+}
+
+/// Accessors of generic messages get default instances from a registry, as
+/// the field type may be a type parameter that has no `default_instance`.
+fn classicalize_optional_message_field(
+    field: &Field,
+    index: usize,
+    generic: bool,
+) -> Result<proc_macro2::TokenStream> {
+    let (ident, ident_str) = field_name(field, index);
+    let ty = wrapped_type(&field.ty, "Option", "optional message fields")?;
     let set = Ident::new(&format!("set_{}", ident_str), Span::call_site());
     let get = Ident::new(&format!("get_{}", ident_str), Span::call_site());
     let take = Ident::new(&format!("take_{}", ident_str), Span::call_site());
     let mutation = Ident::new(&format!("mut_{}", ident_str), Span::call_site());
     let has = Ident::new(&format!("has_{}", ident_str), Span::call_site());
-    quote! {
-        pub fn #set(&mut self, value: #ty) {
-            self.#ident = Some(value);
+    let clear = Ident::new(&format!("clear_{}", ident_str), Span::call_site());
+    // PROST! boxes recursive messages, which should be hidden from accessors.
+    let boxed = boxed_type(ty);
+    let ty = boxed.unwrap_or(ty);
+    let (default_instance, bounds) = if generic {
+        (
+            quote!(_jinkela::__private::default_instance::<#ty>()),
+            quote!(where #ty: Default + Send + Sync + 'static),
+        )
+    } else {
+        (quote!(<#ty>::default_instance()), quote!())
+    };
+    if boxed.is_some() {
+        return Ok(quote! {
+            pub fn #set(&mut self, value: impl Into<_jinkela::SingularPtrField<#ty>>) {
+                self.#ident = value.into().into_option().map(Box::new);
+            }
+
+            pub fn #get(&self) -> &#ty #bounds {
+                match self.#ident {
+                    Some(ref v) => v,
+                    None => #default_instance,
+                }
+            }
+
+            pub fn #mutation(&mut self) -> &mut #ty {
+                self.#ident.get_or_insert_with(|| Box::new(<#ty>::default()))
+            }
+
+            pub fn #take(&mut self) -> #ty {
+                self.#ident.take().map_or_else(|| <#ty>::default(), |v| *v)
+            }
+
+            pub fn #has(&self) -> bool {
+                self.#ident.is_some()
+            }
+
+            pub fn #clear(&mut self) {
+                self.#ident = None;
+            }
+        });
+    }
+    Ok(quote! {
+        pub fn #set(&mut self, value: impl Into<_jinkela::SingularPtrField<#ty>>) {
+            self.#ident = value.into().into_option();
         }
 
-        pub fn #get(&self) -> &#ty {
-            self.#ident.as_ref().unwrap_or_else(|| #ty::default_instance())
+        pub fn #get(&self) -> &#ty #bounds {
+            self.#ident.as_ref().unwrap_or_else(|| #default_instance)
         }
 
         pub fn #mutation(&mut self) -> &mut #ty {
-            self.#ident.get_or_insert_with(|| #ty::default())
+            self.#ident.get_or_insert_with(|| <#ty>::default())
         }
 
         pub fn #take(&mut self) -> #ty {
-            self.#ident.take().unwrap_or_else(|| #ty::default())
+            self.#ident.take().unwrap_or_else(|| <#ty>::default())
         }
 
         pub fn #has(&self) -> bool {
             self.#ident.is_some()
         }
-    }
+
+        pub fn #clear(&mut self) {
+            self.#ident = None;
+        }
+    })
 }
 
-fn classicalize_repeated_field(field: &Field) -> proc_macro2::TokenStream {
-    let ident = field.ident.as_ref().unwrap();
-    let mut ident_str = ident.to_string();
-    if ident_str.starts_with("r#") {
-        ident_str = ident_str[2..].to_owned();
-    }
+/// Gets `T` if ty is `Box<T>`.
+fn boxed_type(ty: &Type) -> Option<&Type> {
+    wrapped_type(ty, "Box", "boxed messages").ok()
+}
+
+/// Setters of repeated messages also accept `RepeatedField` like rust-protobuf,
+/// while repeated scalars are set with `Vec` in both.
+fn classicalize_repeated_field(field: &Field, index: usize, message: bool) -> Result<proc_macro2::TokenStream> {
+    let (ident, ident_str) = field_name(field, index);
     let origin_ty = &field.ty;
-    let ty = match origin_ty {
-        Type::Path(tp) => {
-            let wrapper = tp.path.segments.iter().last().unwrap();
-            assert_eq!(wrapper.ident, "Vec", "expected Vec, but got {:?}", origin_ty);
-            let generic_arg = match wrapper.arguments {
-                PathArguments::AngleBracketed(ref params) => params.args.iter().next().unwrap(),
-                _ => panic!("unexpected token {:?}", origin_ty),
-            };
-            match generic_arg {
-                GenericArgument::Type(ty) => ty,
-                _ => panic!("expected generic, but get {:?}", origin_ty),
-            }
-        },
-        _ => panic!("unexpected type {:?}", origin_ty),
-    };
+    let ty = wrapped_type(origin_ty, "Vec", "repeated fields")?;
     let set = Ident::new(&format!("set_{}", ident_str), Span::call_site());
     let get = Ident::new(&format!("get_{}", ident_str), Span::call_site());
     let take = Ident::new(&format!("take_{}", ident_str), Span::call_site());
     let mutation = Ident::new(&format!("mut_{}", ident_str), Span::call_site());
-    quote! {
-        pub fn #set(&mut self, value: #origin_ty) {
-            self.#ident = value;
+    let clear = Ident::new(&format!("clear_{}", ident_str), Span::call_site());
+    let setter = if message {
+        quote! {
+            pub fn #set(&mut self, value: impl Into<#origin_ty>) {
+                self.#ident = value.into();
+            }
+        }
+    } else {
+        quote! {
+            pub fn #set(&mut self, value: #origin_ty) {
+                self.#ident = value;
+            }
         }
+    };
+    Ok(quote! {
+        #setter
 
         pub fn #get(&self) -> &[#ty] {
             &self.#ident
         }
 
+        pub fn #clear(&mut self) {
+            self.#ident.clear();
+        }
+
         pub fn #mutation(&mut self) -> &mut #origin_ty {
             &mut self.#ident
         }
@@ -113,18 +276,16 @@ fn classicalize_repeated_field(field: &Field) -> proc_macro2::TokenStream {
         pub fn #take(&mut self) -> #origin_ty {
             ::std::mem::replace(&mut self.#ident, ::std::vec::Vec::new())
         }
-    }
+    })
 }
 
-fn classicalize_copy_field(field: &Field) -> proc_macro2::TokenStream {
-    let ident = field.ident.as_ref().unwrap();
-    let mut ident_str = ident.to_string();
-    if ident_str.starts_with("r#") {
-        ident_str = ident_str[2..].to_owned();
-    }
+fn classicalize_copy_field(field: &Field, index: usize, default: Option<proc_macro2::TokenStream>) -> proc_macro2::TokenStream {
+    let (ident, ident_str) = field_name(field, index);
     let ty = &field.ty;
     let set = Ident::new(&format!("set_{}", ident_str), Span::call_site());
     let get = Ident::new(&format!("get_{}", ident_str), Span::call_site());
+    let clear = Ident::new(&format!("clear_{}", ident_str), Span::call_site());
+    let default = default.unwrap_or_else(|| quote!(::std::default::Default::default()));
     quote! {
         pub fn #set(&mut self, value: #ty) {
             self.#ident = value;
@@ -133,19 +294,28 @@ fn classicalize_copy_field(field: &Field) -> proc_macro2::TokenStream {
         pub fn #get(&self) -> #ty {
             self.#ident
         }
+
+        pub fn #clear(&mut self) {
+            self.#ident = #default;
+        }
     }
 }
 
-fn classicalize_string_field(field: &Field) -> proc_macro2::TokenStream {
-    let ident = field.ident.as_ref().unwrap();
-    let mut ident_str = ident.to_string();
-    if ident_str.starts_with("r#") {
-        ident_str = ident_str[2..].to_owned();
-    }
+fn classicalize_string_field(field: &Field, index: usize, default: Option<proc_macro2::TokenStream>) -> proc_macro2::TokenStream {
+    let (ident, ident_str) = field_name(field, index);
     let set = Ident::new(&format!("set_{}", ident_str), Span::call_site());
     let get = Ident::new(&format!("get_{}", ident_str), Span::call_site());
     let take = Ident::new(&format!("take_{}", ident_str), Span::call_site());
+    let clear = Ident::new(&format!("clear_{}", ident_str), Span::call_site());
+    let clear_body = match default {
+        Some(default) => quote!(self.#ident = String::from(#default);),
+        None => quote!(self.#ident.clear();),
+    };
     quote! {
+        pub fn #clear(&mut self) {
+            #clear_body
+        }
+
         pub fn #set(&mut self, value: String) {
             self.#ident = value;
         }
@@ -160,17 +330,25 @@ fn classicalize_string_field(field: &Field) -> proc_macro2::TokenStream {
     }
 }
 
-fn classicalize_bytes_field(field: &Field) -> proc_macro2::TokenStream {
-    let ident = field.ident.as_ref().unwrap();
-    let mut ident_str = ident.to_string();
-    if ident_str.starts_with("r#") {
-        ident_str = ident_str[2..].to_owned();
-    }
+/// The field can be either `Vec<u8>` or `bytes::Bytes`, both of which can be
+/// built from a static slice and dereference to `[u8]`.
+fn classicalize_bytes_field(field: &Field, index: usize, default: Option<proc_macro2::TokenStream>) -> proc_macro2::TokenStream {
+    let (ident, ident_str) = field_name(field, index);
+    let ty = &field.ty;
     let set = Ident::new(&format!("set_{}", ident_str), Span::call_site());
     let get = Ident::new(&format!("get_{}", ident_str), Span::call_site());
     let take = Ident::new(&format!("take_{}", ident_str), Span::call_site());
+    let clear = Ident::new(&format!("clear_{}", ident_str), Span::call_site());
+    let clear_body = match default {
+        Some(default) => quote!(self.#ident = <#ty>::from(#default);),
+        None => quote!(self.#ident.clear();),
+    };
     quote! {
-        pub fn #set(&mut self, value: Vec<u8>) {
+        pub fn #clear(&mut self) {
+            #clear_body
+        }
+
+        pub fn #set(&mut self, value: #ty) {
             self.#ident = value;
         }
 
@@ -178,32 +356,371 @@ fn classicalize_bytes_field(field: &Field) -> proc_macro2::TokenStream {
             &self.#ident
         }
 
-        pub fn #take(&mut self) -> Vec<u8> {
-            ::std::mem::replace(&mut self.#ident, Vec::new())
+        pub fn #take(&mut self) -> #ty {
+            ::std::mem::replace(&mut self.#ident, ::std::default::Default::default())
         }
     }
 }
 
+/// Converts the `default` attribute of a proto2 field to an expression.
+///
+/// PROST! keeps the value as written in the proto file, except that bytes are
+/// written as a byte string literal and enums as the variant name.
+fn default_value(lit: &Lit, ft: &FieldType, scalar: Option<&str>) -> Result<proc_macro2::TokenStream> {
+    let value = match lit {
+        Lit::Str(s) => s.value(),
+        _ => return Err(Error::new_spanned(lit, "expected default value as a string")),
+    };
+    if *ft == FieldType::String {
+        // Whitespace is part of string defaults.
+        let lit = proc_macro2::Literal::string(&value);
+        return Ok(quote!(#lit));
+    }
+    let value = value.trim();
+    let invalid = |ty: &str| Err(Error::new_spanned(lit, format!("invalid default value for {}", ty)));
+    Ok(match ft {
+        FieldType::Bytes => match syn::parse_str::<Lit>(value) {
+            Ok(Lit::ByteStr(b)) => quote!(&#b[..]),
+            _ => return invalid("bytes"),
+        },
+        _ => match scalar {
+            Some("float") | Some("double") => {
+                let float = if scalar == Some("float") { quote!(f32) } else { quote!(f64) };
+                match value {
+                    "inf" => quote!(::std::#float::INFINITY),
+                    "-inf" => quote!(::std::#float::NEG_INFINITY),
+                    "nan" => quote!(::std::#float::NAN),
+                    _ => match value.parse::<f64>() {
+                        Ok(v) if scalar == Some("float") => {
+                            let lit = proc_macro2::Literal::f32_unsuffixed(v as f32);
+                            quote!(#lit)
+                        }
+                        Ok(v) => {
+                            let lit = proc_macro2::Literal::f64_unsuffixed(v);
+                            quote!(#lit)
+                        }
+                        Err(_) => return invalid(scalar.unwrap()),
+                    },
+                }
+            }
+            Some("bool") => match value.parse::<bool>() {
+                Ok(true) => quote!(true),
+                Ok(false) => quote!(false),
+                Err(_) => return invalid("bool"),
+            },
+            _ => {
+                if let Ok(v) = value.parse::<i64>() {
+                    let lit = proc_macro2::Literal::i64_unsuffixed(v);
+                    quote!(#lit)
+                } else if let Ok(v) = value.parse::<u64>() {
+                    let lit = proc_macro2::Literal::u64_unsuffixed(v);
+                    quote!(#lit)
+                } else {
+                    return invalid(scalar.unwrap_or("integer"));
+                }
+            }
+        },
+    })
+}
+
+/// Generates accessors for proto2 optional scalar, string and bytes fields,
+/// which are wrapped in `Option` by PROST!.
+///
+/// Getters return `default` for unset fields if it's declared.
+fn classicalize_proto2_field(
+    field: &Field,
+    index: usize,
+    ft: &FieldType,
+    default: Option<proc_macro2::TokenStream>,
+) -> Result<proc_macro2::TokenStream> {
+    let (ident, ident_str) = field_name(field, index);
+    let ty = wrapped_type(&field.ty, "Option", "proto2 optional fields")?;
+    let set = Ident::new(&format!("set_{}", ident_str), Span::call_site());
+    let get = Ident::new(&format!("get_{}", ident_str), Span::call_site());
+    let take = Ident::new(&format!("take_{}", ident_str), Span::call_site());
+    let mutation = Ident::new(&format!("mut_{}", ident_str), Span::call_site());
+    let has = Ident::new(&format!("has_{}", ident_str), Span::call_site());
+    let clear = Ident::new(&format!("clear_{}", ident_str), Span::call_site());
+    let getter = match ft {
+        FieldType::String => {
+            let default = default.unwrap_or_else(|| quote!(""));
+            quote! {
+                pub fn #get(&self) -> &str {
+                    match self.#ident {
+                        Some(ref v) => v,
+                        None => #default,
+                    }
+                }
+            }
+        }
+        FieldType::Bytes => {
+            let default = default.unwrap_or_else(|| quote!(&[]));
+            quote! {
+                pub fn #get(&self) -> &[u8] {
+                    match self.#ident {
+                        Some(ref v) => v,
+                        None => #default,
+                    }
+                }
+            }
+        }
+        _ => {
+            let default = default.unwrap_or_else(|| quote!(::std::default::Default::default()));
+            quote! {
+                pub fn #get(&self) -> #ty {
+                    match self.#ident {
+                        Some(v) => v,
+                        None => #default,
+                    }
+                }
+            }
+        }
+    };
+    Ok(quote! {
+        pub fn #has(&self) -> bool {
+            self.#ident.is_some()
+        }
+
+        pub fn #clear(&mut self) {
+            self.#ident = None;
+        }
+
+        pub fn #set(&mut self, value: #ty) {
+            self.#ident = Some(value);
+        }
+
+        #getter
+
+        pub fn #mutation(&mut self) -> &mut #ty {
+            self.#ident.get_or_insert_with(::std::default::Default::default)
+        }
+
+        pub fn #take(&mut self) -> #ty {
+            self.#ident.take().unwrap_or_default()
+        }
+    })
+}
 
-fn classicalize_enum_field(field: &Field, lit: &Lit) -> proc_macro2::TokenStream {
-    let ident = field.ident.as_ref().unwrap();
-    let mut ident_str = ident.to_string();
-    if ident_str.starts_with("r#") {
-        ident_str = ident_str[2..].to_owned();
+fn classicalize_map_field(field: &Field, index: usize, lit: &Lit, opts: &Options) -> Result<proc_macro2::TokenStream> {
+    let (ident, ident_str) = field_name(field, index);
+    let ty = &field.ty;
+    let set = Ident::new(&format!("set_{}", ident_str), Span::call_site());
+    let get = Ident::new(&format!("get_{}", ident_str), Span::call_site());
+    let take = Ident::new(&format!("take_{}", ident_str), Span::call_site());
+    let mutation = Ident::new(&format!("mut_{}", ident_str), Span::call_site());
+    let clear = Ident::new(&format!("clear_{}", ident_str), Span::call_site());
+    let value_ty = match lit {
+        Lit::Str(s) => s.value(),
+        _ => return Err(Error::new_spanned(lit, "expected map type as a string")),
+    };
+    let getter = match value_ty.find("enumeration(") {
+        // PROST! already generates `get_` and `insert_` for single entries of
+        // enumerations, so the converted map is returned by `get_*_map`.
+        Some(pos) => {
+            let enum_ty = &value_ty[pos + "enumeration(".len()..];
+            let enum_ty = match enum_ty.find(')') {
+                Some(end) => &enum_ty[..end],
+                None => return Err(Error::new_spanned(lit, "invalid map type")),
+            };
+            let enum_ty: Path = syn::parse_str(enum_ty).map_err(|e| Error::new_spanned(lit, format!("invalid enum type: {}", e)))?;
+            let mut map_ty = ty.clone();
+            if let Type::Path(tp) = &mut map_ty {
+                if let Some(seg) = tp.path.segments.iter_mut().last() {
+                    if let PathArguments::AngleBracketed(params) = &mut seg.arguments {
+                        if let Some(GenericArgument::Type(v)) = params.args.iter_mut().last() {
+                            *v = parse_quote!(#enum_ty);
+                        }
+                    }
+                }
+            }
+            let get_map = Ident::new(&format!("get_{}_map", ident_str), Span::call_site());
+            let unknown = unknown_enum_value(opts, &ident_str, quote!(v), &quote!(<#enum_ty as ::std::default::Default>::default()));
+            quote! {
+                pub fn #get_map(&self) -> #map_ty {
+                    self.#ident.iter().map(|(k, v)| {
+                        let e = match <#enum_ty as _jinkela::GenericEnum>::from_i32(*v) {
+                            Some(e) => e,
+                            None => #unknown,
+                        };
+                        (k.clone(), e)
+                    }).collect()
+                }
+            }
+        }
+        None => quote! {
+            pub fn #get(&self) -> &#ty {
+                &self.#ident
+            }
+        },
+    };
+    Ok(quote! {
+        pub fn #set(&mut self, value: #ty) {
+            self.#ident = value;
+        }
+
+        #getter
+
+        pub fn #clear(&mut self) {
+            self.#ident.clear();
+        }
+
+        pub fn #mutation(&mut self) -> &mut #ty {
+            &mut self.#ident
+        }
+
+        pub fn #take(&mut self) -> #ty {
+            ::std::mem::replace(&mut self.#ident, ::std::default::Default::default())
+        }
+    })
+}
+
+/// Generates the expression that handles an unknown enum value `v` of field `name`.
+fn unknown_enum_value(
+    opts: &Options,
+    name: &str,
+    v: proc_macro2::TokenStream,
+    default: &proc_macro2::TokenStream,
+) -> proc_macro2::TokenStream {
+    if opts.unknown_enum_as_default {
+        quote!(#default)
+    } else {
+        let msg = format!("Unexpected enum value for {}: {{}}", name);
+        quote!(panic!(#msg, #v))
     }
+}
+
+fn classicalize_repeated_enum_field(field: &Field, index: usize, lit: &Lit, opts: &Options) -> Result<proc_macro2::TokenStream> {
+    let (ident, ident_str) = field_name(field, index);
+    let ty = parse_type_path(lit, "enum type")?;
+    let set = Ident::new(&format!("set_{}", ident_str), Span::call_site());
+    let get = Ident::new(&format!("get_{}", ident_str), Span::call_site());
+    let try_get = Ident::new(&format!("try_get_{}", ident_str), Span::call_site());
+    let take = Ident::new(&format!("take_{}", ident_str), Span::call_site());
+    let mutation = Ident::new(&format!("mut_{}", ident_str), Span::call_site());
+    let clear = Ident::new(&format!("clear_{}", ident_str), Span::call_site());
+    let unknown = unknown_enum_value(opts, &ident_str, quote!(v), &quote!(<#ty as ::std::default::Default>::default()));
+    // Values are stored as i32, so they are converted on every access.
+    Ok(quote! {
+        pub fn #set(&mut self, value: Vec<#ty>) {
+            self.#ident = value.iter().map(_jinkela::GenericEnum::value).collect();
+        }
+
+        pub fn #get(&self) -> Vec<#ty> {
+            self.#ident.iter().map(|v| match <#ty as _jinkela::GenericEnum>::from_i32(*v) {
+                Some(e) => e,
+                None => #unknown,
+            }).collect()
+        }
+
+        /// Returns the first unknown value as error.
+        pub fn #try_get(&self) -> Result<Vec<#ty>, i32> {
+            self.#ident.iter().map(|v| <#ty as _jinkela::GenericEnum>::from_i32(*v).ok_or(*v)).collect()
+        }
+
+        pub fn #mutation(&mut self) -> &mut Vec<i32> {
+            &mut self.#ident
+        }
+
+        pub fn #take(&mut self) -> Vec<#ty> {
+            let v = self.#get();
+            self.#ident.clear();
+            v
+        }
+
+        pub fn #clear(&mut self) {
+            self.#ident.clear();
+        }
+    })
+}
+
+/// Generates accessors for a single enumeration field.
+///
+/// PROST! already generates `set_x` for enumeration fields of the messages it
+/// derives, which always have tags, so the setter is only generated for fields
+/// without tags.
+fn classicalize_enum_field(
+    field: &Field,
+    index: usize,
+    lit: &Lit,
+    proto2: bool,
+    default: Option<&Lit>,
+    tagged: bool,
+    opts: &Options,
+) -> Result<proc_macro2::TokenStream> {
+    let (ident, ident_str) = field_name(field, index);
     let get = Ident::new(&format!("get_{}", ident_str), Span::call_site());
-    let ty = match lit {
-        Lit::Str(s) => syn::parse_str::<Path>(&s.value()).unwrap(),
-        _ => panic!("expected enum type, but got {:?}", lit),
+    let try_get = Ident::new(&format!("try_get_{}", ident_str), Span::call_site());
+    let ty = parse_type_path(lit, "enum type")?;
+    let set = Ident::new(&format!("set_{}", ident_str), Span::call_site());
+    let setter = match (tagged, proto2) {
+        (true, _) => quote!(),
+        (false, true) => quote! {
+            pub fn #set(&mut self, value: #ty) {
+                self.#ident = Some(_jinkela::GenericEnum::value(&value));
+            }
+        },
+        (false, false) => quote! {
+            pub fn #set(&mut self, value: #ty) {
+                self.#ident = _jinkela::GenericEnum::value(&value);
+            }
+        },
     };
-    quote! {
+    let clear = Ident::new(&format!("clear_{}", ident_str), Span::call_site());
+    let default = match default {
+        Some(Lit::Str(s)) => {
+            let variant: Ident = s.parse()?;
+            quote!(#ty::#variant)
+        }
+        Some(lit) => return Err(Error::new_spanned(lit, "expected default variant as a string")),
+        None => quote!(<#ty as ::std::default::Default>::default()),
+    };
+    let unknown = unknown_enum_value(opts, &ident_str, quote!(v), &default);
+    let getter = quote! {
         pub fn #get(&self) -> #ty {
-            match #ty::from_i32(self.#ident) {
-                Some(v) => v,
-                None => panic!("Unexpected enum value for #lit: {}", self.#ident),
+            match self.#try_get() {
+                Ok(v) => v,
+                Err(v) => #unknown,
             }
         }
+    };
+    if proto2 {
+        let has = Ident::new(&format!("has_{}", ident_str), Span::call_site());
+        return Ok(quote! {
+            pub fn #has(&self) -> bool {
+                self.#ident.is_some()
+            }
+
+            pub fn #clear(&mut self) {
+                self.#ident = None;
+            }
+
+            #setter
+
+            #getter
+
+            /// Returns the value as error if it's not defined by the enum.
+            pub fn #try_get(&self) -> Result<#ty, i32> {
+                match self.#ident {
+                    Some(v) => <#ty as _jinkela::GenericEnum>::from_i32(v).ok_or(v),
+                    None => Ok(#default),
+                }
+            }
+        });
     }
+    Ok(quote! {
+        #getter
+
+        /// Returns the value as error if it's not defined by the enum.
+        pub fn #try_get(&self) -> Result<#ty, i32> {
+            <#ty as _jinkela::GenericEnum>::from_i32(self.#ident).ok_or(self.#ident)
+        }
+
+        #setter
+
+        pub fn #clear(&mut self) {
+            self.#ident = _jinkela::GenericEnum::value(&#default);
+        }
+    })
 }
 
 #[derive(PartialEq)]
@@ -220,7 +737,12 @@ enum Frequency {
     Repeated,
 }
 
-fn classicalize_accessors(field: &Field) -> Option<proc_macro2::TokenStream> {
+fn classicalize_accessors(
+    field: &Field,
+    index: usize,
+    generic: bool,
+    opts: &Options,
+) -> Result<Option<proc_macro2::TokenStream>> {
     for a in &field.attrs {
         for m in a.interpret_meta() {
             match m {
@@ -228,46 +750,128 @@ fn classicalize_accessors(field: &Field) -> Option<proc_macro2::TokenStream> {
                     if ident == "prost" {
                         let mut ft = FieldType::Copyable;
                         let mut freq = Frequency::Optional;
+                        // Only proto2 optional fields and messages are marked optional.
+                        let mut proto2 = false;
+                        let mut enumeration = None;
+                        let mut scalar = None;
+                        let mut default = None;
+                        let mut tagged = false;
                         for n in nested {
                             match n {
                                 NestedMeta::Meta(Meta::Word(w)) => if w == "message" {
                                     ft = FieldType::Message;
                                 } else if w == "optional" {
                                     freq = Frequency::Optional;
+                                    proto2 = true;
                                 } else if w == "repeated" {
                                     freq = Frequency::Repeated;
                                 } else if w == "bytes" {
                                     ft = FieldType::Bytes;
                                 } else if w == "string" {
                                     ft = FieldType::String;
+                                } else if scalar.is_none() {
+                                    // The type always comes first.
+                                    scalar = Some(w.to_string());
                                 }
                                 NestedMeta::Meta(Meta::NameValue(nv)) => if nv.ident == "enumeration" {
-                                    return Some(classicalize_enum_field(field, &nv.lit))
+                                    enumeration = Some(nv.lit);
+                                } else if nv.ident == "bytes" {
+                                    // Accessors follow the field type instead of `"vec"` or `"bytes"`.
+                                    ft = FieldType::Bytes;
+                                } else if nv.ident == "default" {
+                                    default = Some(nv.lit);
+                                } else if nv.ident == "tag" {
+                                    tagged = true;
+                                } else if nv.ident == "map" || nv.ident == "btree_map" {
+                                    return classicalize_map_field(field, index, &nv.lit, opts).map(Some);
+                                } else if nv.ident == "oneof" {
+                                    // Accessors of oneof are generated by `classicalize_oneof_field`.
+                                    return Ok(None);
                                 }
                                 _ => ()
                             }
                         }
-                        return Some(match freq {
-                            Frequency::Repeated => classicalize_repeated_field(field),
+                        if let Some(lit) = enumeration {
+                            return match freq {
+                                Frequency::Repeated => classicalize_repeated_enum_field(field, index, &lit, opts),
+                                Frequency::Optional => {
+                                    classicalize_enum_field(field, index, &lit, proto2, default.as_ref(), tagged, opts)
+                                }
+                            }.map(Some);
+                        }
+                        let default = match default {
+                            Some(d) => Some(default_value(&d, &ft, scalar.as_deref())?),
+                            None => None,
+                        };
+                        return match freq {
+                            Frequency::Repeated => classicalize_repeated_field(field, index, ft == FieldType::Message),
                             Frequency::Optional => {
                                 match ft {
-                                    FieldType::Message => classicalize_optional_message_field(field),
-                                    FieldType::Copyable => classicalize_copy_field(field),
-                                    FieldType::String => classicalize_string_field(field),
-                                    FieldType::Bytes => classicalize_bytes_field(field),
+                                    FieldType::Message => classicalize_optional_message_field(field, index, generic),
+                                    _ if proto2 => classicalize_proto2_field(field, index, &ft, default),
+                                    FieldType::Copyable => Ok(classicalize_copy_field(field, index, default)),
+                                    FieldType::String => Ok(classicalize_string_field(field, index, default)),
+                                    FieldType::Bytes => Ok(classicalize_bytes_field(field, index, default)),
                                 }
                             }
-                        })
+                        }.map(Some);
                     }
                 },
                 _ => (),
             }
         }
     }
-    None
+    Ok(None)
 }
 
-fn classicalize_struct(ident: Ident, s: DataStruct) -> proc_macro2::TokenStream {
+/// Invokes the macro generated by `classicalize_oneof` to add accessors of the
+/// oneof variants to the message.
+fn classicalize_oneof_field(
+    msg: &Ident,
+    generics: &Generics,
+    field: &Field,
+    index: usize,
+    opts: &Options,
+) -> Result<Option<proc_macro2::TokenStream>> {
+    for a in &field.attrs {
+        if let Some(Meta::List(MetaList { ident, nested, .. })) = a.interpret_meta() {
+            if ident != "prost" {
+                continue;
+            }
+            for n in nested {
+                if let NestedMeta::Meta(Meta::NameValue(nv)) = n {
+                    if nv.ident != "oneof" {
+                        continue;
+                    }
+                    let path = parse_type_path(&nv.lit, "oneof type")?;
+                    let (member, _) = field_name(field, index);
+                    let mut segments: Vec<_> = path.segments.iter().map(|s| s.ident.clone()).collect();
+                    let oneof = segments.pop().unwrap();
+                    let mac = Ident::new(&format!("__jinkela_oneof_{}", oneof), Span::call_site());
+                    let (modules, modules2) = (&segments, &segments);
+                    let unknown = if opts.unknown_enum_as_default { quote!(default) } else { quote!(panic) };
+                    // Generics are passed as is, so that the macro doesn't need to parse them.
+                    let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
+                    let predicates = where_clause.map(|w| &w.predicates);
+                    return Ok(Some(quote! {
+                        #(#modules::)*#mac!(
+                            [#impl_generics] #msg #ty_generics [#predicates],
+                            #member, #unknown, #(#modules2)::*
+                        );
+                    }));
+                }
+            }
+        }
+    }
+    Ok(None)
+}
+
+fn classicalize_struct(
+    ident: Ident,
+    generics: Generics,
+    s: DataStruct,
+    opts: Options,
+) -> Result<proc_macro2::TokenStream> {
     let fields = match s {
         DataStruct {
             fields: Fields::Named(FieldsNamed { named: fields, .. }),
@@ -288,62 +892,455 @@ fn classicalize_struct(ident: Ident, s: DataStruct) -> proc_macro2::TokenStream
 
     // Put impls in a const, so that 'extern crate' can be used.
     let dummy_const = Ident::new(&format!("{}_CLASSICAL_MESSAGE", ident), Span::call_site());
+    let import = opts.import_jinkela();
 
-    let methods = fields
-        .iter()
-        .flat_map(classicalize_accessors)
-        .collect::<Vec<_>>();
+    let mut methods = Vec::with_capacity(fields.len());
+    let mut oneofs = Vec::new();
+    for (i, f) in fields.iter().enumerate() {
+        methods.extend(classicalize_accessors(f, i, !generics.params.is_empty(), &opts)?);
+        oneofs.extend(classicalize_oneof_field(&ident, &generics, f, i, &opts)?);
+    }
+    let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
     let methods = if methods.is_empty() {
         quote!()
     } else {
         quote! {
             #[allow(dead_code)]
-            impl #ident {
+            impl #impl_generics #ident #ty_generics #where_clause {
                 #(#methods)*
             }
         }
     };
-
-    quote! {
-        #[allow(non_snake_case, unused_attributes)]
-        const #dummy_const: () = {
-            extern crate prost as _prost;
-            extern crate bytes as _bytes;
-            extern crate lazy_static;
-
+    // Statics can't be generic, so generic messages share a registry instead.
+    let default_instance = if generics.params.is_empty() {
+        quote! {
             impl #ident {
                 pub fn default_instance() -> &'static #ident {
-                    lazy_static::lazy_static! {
+                    _jinkela::__private::lazy_static::lazy_static! {
                         static ref INSTANCE: #ident = #ident::default();
                     }
                     &*INSTANCE
                 }
             }
+        }
+    } else {
+        quote! {
+            impl #impl_generics #ident #ty_generics #where_clause {
+                pub fn default_instance() -> &'static Self
+                where
+                    Self: Default + Send + Sync + 'static,
+                {
+                    _jinkela::__private::default_instance::<Self>()
+                }
+            }
+        }
+    };
+
+    // A blanket impl for PROST! messages would conflict with the one for
+    // rust-protobuf messages, so each message gets its own.
+    let mut message_generics = generics.clone();
+    message_generics.make_where_clause().predicates.push(parse_quote! {
+        for<'a> #ident #ty_generics: _jinkela::__private::prost::Message + Default
+    });
+    let (_, _, message_where_clause) = message_generics.split_for_impl();
+    let message = quote! {
+        impl #impl_generics _jinkela::GenericMessage for #ident #ty_generics #message_where_clause {
+            type Error = _jinkela::ProtobufError;
+
+            #[inline]
+            fn compute_size(&self) -> usize {
+                _jinkela::prost::compute_size(self)
+            }
+
+            #[inline]
+            fn encode_into(&self, buf: &mut Vec<u8>) -> Result<(), _jinkela::ProtobufError> {
+                _jinkela::prost::encode_into(self, buf)
+            }
+
+            #[inline]
+            fn encode_into_buf<B: _jinkela::__private::bytes::BufMut>(
+                &self,
+                buf: &mut B,
+            ) -> Result<(), _jinkela::ProtobufError> {
+                _jinkela::prost::encode_into_buf(self, buf)
+            }
+
+            #[inline]
+            fn encode_into_writer<W: ::std::io::Write>(&self, w: &mut W) -> Result<(), _jinkela::ProtobufError> {
+                _jinkela::prost::encode_into_writer(self, w)
+            }
+
+            #[inline]
+            fn decode_from(data: &[u8]) -> Result<Self, _jinkela::ProtobufError> {
+                _jinkela::prost::decode_from(data)
+            }
+
+            #[inline]
+            fn decode_from_buf<B: _jinkela::__private::bytes::Buf>(buf: B) -> Result<Self, _jinkela::ProtobufError> {
+                _jinkela::prost::decode_from_buf(buf)
+            }
+
+            #[inline]
+            fn decode_from_reader<R: ::std::io::Read>(r: &mut R) -> Result<Self, _jinkela::ProtobufError> {
+                _jinkela::prost::decode_from_reader(r)
+            }
+
+            #[inline]
+            fn merge_from(&mut self, data: &[u8]) -> Result<(), _jinkela::ProtobufError> {
+                _jinkela::prost::merge_from(self, data)
+            }
+
+            #[inline]
+            fn clear(&mut self) {
+                _jinkela::prost::clear(self)
+            }
+
+            #[inline]
+            fn compute_length_delimited_size(&self) -> usize {
+                _jinkela::prost::compute_length_delimited_size(self)
+            }
+
+            #[inline]
+            fn encode_length_delimited_into(&self, buf: &mut Vec<u8>) -> Result<(), _jinkela::ProtobufError> {
+                _jinkela::prost::encode_length_delimited_into(self, buf)
+            }
+
+            #[inline]
+            fn decode_length_delimited_from(data: &[u8]) -> Result<Self, _jinkela::ProtobufError> {
+                _jinkela::prost::decode_length_delimited_from(data)
+            }
+        }
+    };
+
+    Ok(quote! {
+        #[allow(non_snake_case, unused_attributes)]
+        const #dummy_const: () = {
+            #import
+
+            #default_instance
+
+            #message
 
             #methods
+
+            #(#oneofs)*
+        };
+    })
+}
+
+/// Gets the name in the proto file set by `#[jinkela(name = "FOO_BAR")]` on a
+/// variant, which jinkela-build adds from the descriptors.
+fn variant_name(variant: &Variant) -> Result<Option<String>> {
+    let mut name = None;
+    for a in &variant.attrs {
+        let nested = match a.interpret_meta() {
+            Some(Meta::List(MetaList { ident, nested, .. })) if ident == "jinkela" => nested,
+            _ => continue,
+        };
+        for n in nested {
+            match n {
+                NestedMeta::Meta(Meta::NameValue(ref nv)) if nv.ident == "name" => match nv.lit {
+                    Lit::Str(ref s) => name = Some(s.value()),
+                    _ => return Err(Error::new_spanned(&nv.lit, "expected name as a string")),
+                },
+                _ => return Err(Error::new_spanned(n, "unknown jinkela attribute")),
+            }
+        }
+    }
+    Ok(name)
+}
+
+/// Converts a variant name like `FooBar` back to the proto style `FOO_BAR`.
+fn proto_name(ident: &str) -> String {
+    let chars: Vec<char> = ident.chars().collect();
+    let mut name = String::with_capacity(ident.len() + 4);
+    for (i, c) in chars.iter().enumerate() {
+        if i > 0 && c.is_uppercase() {
+            let prev = chars[i - 1];
+            let next_lower = chars.get(i + 1).is_some_and(|n| n.is_lowercase());
+            if prev.is_lowercase() || prev.is_numeric() || (prev.is_uppercase() && next_lower) {
+                name.push('_');
+            }
+        }
+        name.extend(c.to_uppercase());
+    }
+    name
+}
+
+/// Generates rust-protobuf style accessors for a variant of oneof.
+///
+/// The accessors are expanded in the module of the message, so types are referred
+/// through aliases defined next to the oneof. `$module` is the path from the
+/// message to the oneof.
+fn classicalize_oneof_variant(
+    oneof: &Ident,
+    variant: &Variant,
+) -> Result<(proc_macro2::TokenStream, proc_macro2::TokenStream)> {
+    let v = &variant.ident;
+    let name = proto_name(&v.to_string()).to_lowercase();
+    let set = Ident::new(&format!("set_{}", name), Span::call_site());
+    let get = Ident::new(&format!("get_{}", name), Span::call_site());
+    let take = Ident::new(&format!("take_{}", name), Span::call_site());
+    let mutation = Ident::new(&format!("mut_{}", name), Span::call_site());
+    let has = Ident::new(&format!("has_{}", name), Span::call_site());
+    let clear = Ident::new(&format!("clear_{}", name), Span::call_site());
+    let alias = Ident::new(&format!("__jinkela_{}_{}", oneof, v), Span::call_site());
+    let ty = match variant.fields {
+        Fields::Unnamed(FieldsUnnamed { ref unnamed, .. }) if unnamed.len() == 1 => &unnamed[0].ty,
+        _ => return Err(Error::new_spanned(&variant.fields, "expected a single unnamed field in oneof variant")),
+    };
+
+    let mut ft = FieldType::Copyable;
+    let mut enumeration = None;
+    for a in &variant.attrs {
+        if let Some(Meta::List(MetaList { ident, nested, .. })) = a.interpret_meta() {
+            if ident != "prost" {
+                continue;
+            }
+            for n in nested {
+                match n {
+                    NestedMeta::Meta(Meta::Word(w)) => if w == "message" {
+                        ft = FieldType::Message;
+                    } else if w == "bytes" {
+                        ft = FieldType::Bytes;
+                    } else if w == "string" {
+                        ft = FieldType::String;
+                    }
+                    NestedMeta::Meta(Meta::NameValue(nv)) => if nv.ident == "enumeration" {
+                        enumeration = Some(parse_type_path(&nv.lit, "enum type")?);
+                    } else if nv.ident == "bytes" {
+                        ft = FieldType::Bytes;
+                    }
+                    _ => (),
+                }
+            }
+        }
+    }
+
+    // Recursive messages are boxed, aliases refer to the message instead.
+    let boxed = ft == FieldType::Message && boxed_type(ty).is_some();
+    let ty = if boxed { boxed_type(ty).unwrap() } else { ty };
+    let (wrap, unwrap) = if boxed {
+        (quote!(Box::new(value)), quote!(*v))
+    } else {
+        (quote!(value), quote!(v))
+    };
+
+    let enum_alias = Ident::new(&format!("__jinkela_{}_{}_enum", oneof, v), Span::call_site());
+    let mut aliases = quote! {
+        #[doc(hidden)]
+        pub(crate) type #alias = #ty;
+    };
+    if let Some(ref e) = enumeration {
+        aliases.extend(quote! {
+            #[doc(hidden)]
+            pub(crate) type #enum_alias = #e;
+        });
+    }
+
+    let common = quote! {
+        pub fn #has(&self) -> bool {
+            match self.$field {
+                Some($($module::)*#oneof::#v(..)) => true,
+                _ => false,
+            }
+        }
+
+        pub fn #clear(&mut self) {
+            self.$field = None;
+        }
+    };
+    let accessors = if enumeration.is_some() {
+        let try_get = Ident::new(&format!("try_get_{}", name), Span::call_site());
+        let mac = Ident::new(&format!("__jinkela_oneof_{}", oneof), Span::call_site());
+        // The macro is expanded in the const of the message, which imports `_jinkela`.
+        quote! {
+            pub fn #set(&mut self, value: $($module::)*#enum_alias) {
+                self.$field = Some($($module::)*#oneof::#v(_jinkela::GenericEnum::value(&value)));
+            }
+
+            pub fn #get(&self) -> $($module::)*#enum_alias {
+                match self.#try_get() {
+                    Ok(e) => e,
+                    Err(v) => $($module::)*#mac!(@unknown $unknown, #name, v),
+                }
+            }
+
+            /// Returns the value as error if it's not defined by the enum.
+            pub fn #try_get(&self) -> Result<$($module::)*#enum_alias, i32> {
+                match self.$field {
+                    Some($($module::)*#oneof::#v(v)) => {
+                        <$($module::)*#enum_alias as _jinkela::GenericEnum>::from_i32(v).ok_or(v)
+                    }
+                    _ => Ok(::std::default::Default::default()),
+                }
+            }
+        }
+    } else if ft == FieldType::Copyable {
+        quote! {
+            pub fn #set(&mut self, value: $($module::)*#alias) {
+                self.$field = Some($($module::)*#oneof::#v(value));
+            }
+
+            pub fn #get(&self) -> $($module::)*#alias {
+                match self.$field {
+                    Some($($module::)*#oneof::#v(v)) => v,
+                    _ => ::std::default::Default::default(),
+                }
+            }
+        }
+    } else {
+        let (get_ty, default) = match ft {
+            FieldType::Message => (
+                quote!(&$($module::)*#alias),
+                quote!(<$($module::)*#alias>::default_instance()),
+            ),
+            FieldType::String => (quote!(&str), quote!("")),
+            _ => (quote!(&[u8]), quote!(&[])),
         };
+        quote! {
+            pub fn #set(&mut self, value: $($module::)*#alias) {
+                self.$field = Some($($module::)*#oneof::#v(#wrap));
+            }
+
+            pub fn #get(&self) -> #get_ty {
+                match self.$field {
+                    Some($($module::)*#oneof::#v(ref v)) => v,
+                    _ => #default,
+                }
+            }
+
+            pub fn #mutation(&mut self) -> &mut $($module::)*#alias {
+                if !self.#has() {
+                    self.$field = Some($($module::)*#oneof::#v(::std::default::Default::default()));
+                }
+                match self.$field {
+                    Some($($module::)*#oneof::#v(ref mut v)) => v,
+                    _ => unreachable!(),
+                }
+            }
+
+            pub fn #take(&mut self) -> $($module::)*#alias {
+                if self.#has() {
+                    if let Some($($module::)*#oneof::#v(v)) = self.$field.take() {
+                        return #unwrap;
+                    }
+                }
+                ::std::default::Default::default()
+            }
+        }
+    };
+    Ok((aliases, quote!(#common #accessors)))
+}
+
+/// Oneofs are enums with data carrying variants. Accessors are generated on the
+/// message, which is unknown here, so a macro is defined for the message to invoke.
+fn classicalize_oneof(ident: Ident, s: DataEnum) -> Result<proc_macro2::TokenStream> {
+    let mac = Ident::new(&format!("__jinkela_oneof_{}", ident), Span::call_site());
+    let mut aliases = Vec::with_capacity(s.variants.len());
+    let mut methods = Vec::with_capacity(s.variants.len());
+    for v in &s.variants {
+        let (alias, method) = classicalize_oneof_variant(&ident, v)?;
+        aliases.push(alias);
+        methods.push(method);
     }
+    Ok(quote! {
+        #(#aliases)*
+
+        #[doc(hidden)]
+        macro_rules! #mac {
+            (@unknown panic, $name:expr, $v:expr) => {
+                panic!("Unexpected enum value for {}: {}", $name, $v)
+            };
+            (@unknown default, $name:expr, $v:expr) => {
+                ::std::default::Default::default()
+            };
+            (
+                [$($generics:tt)*] $parent:ty [$($predicates:tt)*],
+                $field:tt, $unknown:ident, $($module:ident)::*
+            ) => {
+                #[allow(dead_code)]
+                impl $($generics)* $parent where $($predicates)* {
+                    #(#methods)*
+                }
+            };
+        }
+
+        #[doc(hidden)]
+        pub(crate) use #mac;
+    })
 }
 
-fn classicalize_enum(ident: Ident, s: DataEnum) -> proc_macro2::TokenStream {
+fn classicalize_enum(ident: Ident, s: DataEnum, opts: Options) -> Result<proc_macro2::TokenStream> {
+    if s.variants.iter().any(|v| v.fields != Fields::Unit) {
+        return classicalize_oneof(ident, s);
+    }
     let dummy_const = Ident::new(&format!("{}_CLASSICAL_ENUMERATION", ident), Span::call_site());
+    let import = opts.import_jinkela();
 
     // Map the variants into 'fields'.
     let mut variants = Vec::with_capacity(s.variants.len());
+    let mut names = Vec::with_capacity(s.variants.len());
+    let mut patterns = Vec::with_capacity(s.variants.len());
+    let enum_name = proto_name(&ident.to_string());
     for v in s.variants {
         let value_ident = &v.ident;
         variants.push(quote! { #ident::#value_ident});
+        match variant_name(&v)? {
+            Some(name) => {
+                patterns.push(quote!(#name));
+                names.push(name);
+            }
+            None => {
+                // Guess the name from the variant. PROST! strips the enum name
+                // from variants, so accept the full name too.
+                let name = proto_name(&value_ident.to_string());
+                let prefixed = format!("{}_{}", enum_name, name);
+                patterns.push(quote!(#name | #prefixed));
+                names.push(name);
+            }
+        }
     }
-    quote! {
-        #[allow(non_snake_case, unused_attributes)]
+    // quote can't repeat the same variable twice in a repetition.
+    let (variants, variants2) = (&variants, &variants);
+    Ok(quote! {
+        #[allow(non_snake_case, unused_attributes, unreachable_patterns)]
         const #dummy_const: () = {
-            extern crate jinkela as _jinkela;
+            #import
 
             impl _jinkela::GenericEnum for #ident {
                 fn values() -> &'static [#ident] {
                     &[#(#variants,)*]
                 }
+
+                fn value(&self) -> i32 {
+                    match self {
+                        #(#variants => #variants2 as i32,)*
+                    }
+                }
+
+                fn from_i32(v: i32) -> Option<#ident> {
+                    #(
+                        if v == #variants as i32 {
+                            return Some(#variants2);
+                        }
+                    )*
+                    None
+                }
+
+                fn name(&self) -> &'static str {
+                    match self {
+                        #(#variants => #names,)*
+                    }
+                }
+
+                fn from_str_name(name: &str) -> Option<#ident> {
+                    match name {
+                        #(#patterns => Some(#variants),)*
+                        _ => None,
+                    }
+                }
             }
         };
-    }
+    })
 }
@@ -0,0 +1,73 @@
+//! Free functions named after the ones in rust-protobuf, so that code written
+//! against rust-protobuf doesn't need to change with the backend.
+//!
+//! Messages get `new`, `compute_size`, `write_to_bytes` and `merge_from_bytes`
+//! from [`Message`] for the same reason.
+
+use crate::GenericMessage;
+#[cfg(feature = "bytes")]
+use crate::ZeroCopyMessage;
+#[cfg(feature = "bytes")]
+use bytes::Bytes;
+use std::io::Read;
+
+/// Parse a message from bytes.
+pub fn parse_from_bytes<M: GenericMessage>(bytes: &[u8]) -> Result<M, M::Error> {
+    M::decode_from(bytes)
+}
+
+/// Parse a message from bytes, bytes fields share the buffer.
+#[cfg(feature = "bytes")]
+pub fn parse_from_carllerche_bytes<M: ZeroCopyMessage>(bytes: &Bytes) -> Result<M, M::Error> {
+    M::decode_from_bytes(bytes.clone())
+}
+
+/// Parse a length delimited message from bytes.
+pub fn parse_length_delimited_from_bytes<M: GenericMessage>(bytes: &[u8]) -> Result<M, M::Error> {
+    M::decode_length_delimited_from(bytes)
+}
+
+/// Parse a message from the reader until EOF.
+pub fn parse_from_reader<M: GenericMessage>(mut reader: &mut dyn Read) -> Result<M, M::Error> {
+    M::decode_from_reader(&mut reader)
+}
+
+/// Methods named after the ones in `protobuf::Message`.
+///
+/// `compute_size` is also a method of `GenericMessage`, so import this trait
+/// where `protobuf::Message` was imported instead of along with `GenericMessage`.
+pub trait Message: GenericMessage {
+    /// Creates an empty message.
+    fn new() -> Self
+    where
+        Self: Default,
+    {
+        Self::default()
+    }
+
+    /// Gets the size of the encoded message.
+    fn compute_size(&self) -> u32 {
+        GenericMessage::compute_size(self) as u32
+    }
+
+    /// Encodes the message into a new vector.
+    fn write_to_bytes(&self) -> Result<Vec<u8>, Self::Error> {
+        let mut buf = Vec::with_capacity(GenericMessage::compute_size(self));
+        self.encode_into(&mut buf)?;
+        Ok(buf)
+    }
+
+    /// Encodes the message with a length prefix into a new vector.
+    fn write_length_delimited_to_bytes(&self) -> Result<Vec<u8>, Self::Error> {
+        let mut buf = Vec::with_capacity(self.compute_length_delimited_size());
+        self.encode_length_delimited_into(&mut buf)?;
+        Ok(buf)
+    }
+
+    /// Merges the encoded message into this one.
+    fn merge_from_bytes(&mut self, data: &[u8]) -> Result<(), Self::Error> {
+        self.merge_from(data)
+    }
+}
+
+impl<M: GenericMessage> Message for M {}
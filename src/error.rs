@@ -0,0 +1,169 @@
+use std::error::Error;
+use std::{fmt, io};
+
+/// The category of a `ProtobufError`, shared by all backends.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ErrorKind {
+    /// The input ends in the middle of a message.
+    TruncatedInput,
+    /// A varint is malformed or too long.
+    InvalidVarint,
+    /// A field uses an unknown or unexpected wire type.
+    InvalidWireType,
+    /// A field key has an invalid tag.
+    InvalidTag,
+    /// A string field is not valid UTF-8.
+    InvalidUtf8,
+    /// The output buffer doesn't have enough capacity for the message.
+    BufferTooSmall,
+    /// Messages are nested too deeply.
+    RecursionLimit,
+    /// An enumeration field has a value that is not defined by the enum.
+    UnknownEnumValue,
+    /// A proto2 required field is not set.
+    MissingRequiredField,
+    /// Reading from or writing to an io stream failed.
+    Io,
+    /// Any other error reported by the backend.
+    Other,
+}
+
+impl ErrorKind {
+    fn as_str(self) -> &'static str {
+        match self {
+            ErrorKind::TruncatedInput => "truncated input",
+            ErrorKind::InvalidVarint => "invalid varint",
+            ErrorKind::InvalidWireType => "invalid wire type",
+            ErrorKind::InvalidTag => "invalid tag",
+            ErrorKind::InvalidUtf8 => "invalid UTF-8",
+            ErrorKind::BufferTooSmall => "buffer too small",
+            ErrorKind::RecursionLimit => "recursion limit reached",
+            ErrorKind::UnknownEnumValue => "unknown enum value",
+            ErrorKind::MissingRequiredField => "missing required field",
+            ErrorKind::Io => "io error",
+            ErrorKind::Other => "protobuf error",
+        }
+    }
+}
+
+impl fmt::Display for ErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// The error returned by all backends.
+///
+/// Match on `kind()` to handle errors without knowing which backend is in use.
+#[derive(Debug)]
+pub struct ProtobufError {
+    kind: ErrorKind,
+    message: String,
+    path: Option<String>,
+    source: Option<Box<dyn Error + Send + Sync>>,
+}
+
+impl ProtobufError {
+    /// Create an error of kind with a detail message.
+    pub fn new(kind: ErrorKind, message: impl Into<String>) -> ProtobufError {
+        ProtobufError {
+            kind,
+            message: message.into(),
+            path: None,
+            source: None,
+        }
+    }
+
+    /// Create an error of kind that is caused by source.
+    pub fn with_source(
+        kind: ErrorKind,
+        source: impl Into<Box<dyn Error + Send + Sync>>,
+    ) -> ProtobufError {
+        let source = source.into();
+        ProtobufError {
+            kind,
+            message: source.to_string(),
+            path: None,
+            source: Some(source),
+        }
+    }
+
+    /// Get the category of the error.
+    pub fn kind(&self) -> ErrorKind {
+        self.kind
+    }
+
+    /// Get the detail message of the error.
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+
+    /// Get the path of the field that fails to decode, like `Request.mutations[3].key`.
+    ///
+    /// Indexes are shown for repeated fields. Backends without reflection only
+    /// know a field is repeated when it occurs more than once in the input.
+    pub fn path(&self) -> Option<&str> {
+        self.path.as_deref()
+    }
+
+    #[cfg(any(
+        feature = "protobuf-codec",
+        feature = "protobuf3-codec",
+        feature = "prost-codec",
+        feature = "quick-protobuf-codec"
+    ))]
+    pub(crate) fn set_path(&mut self, path: String) {
+        self.path = Some(path);
+    }
+}
+
+impl fmt::Display for ProtobufError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.kind)?;
+        if let Some(ref path) = self.path {
+            write!(f, " at {}", path)?;
+        }
+        if !self.message.is_empty() {
+            write!(f, ": {}", self.message)?;
+        }
+        Ok(())
+    }
+}
+
+impl Error for ProtobufError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self.source {
+            Some(ref e) => Some(&**e),
+            None => None,
+        }
+    }
+}
+
+impl From<io::Error> for ProtobufError {
+    fn from(e: io::Error) -> ProtobufError {
+        let kind = match e.kind() {
+            io::ErrorKind::UnexpectedEof => ErrorKind::TruncatedInput,
+            io::ErrorKind::WriteZero => ErrorKind::BufferTooSmall,
+            _ => ErrorKind::Io,
+        };
+        ProtobufError::with_source(kind, e)
+    }
+}
+
+impl From<ProtobufError> for io::Error {
+    fn from(e: ProtobufError) -> io::Error {
+        let kind = match e.kind {
+            ErrorKind::Io => match e.source {
+                Some(source) => match source.downcast::<io::Error>() {
+                    Ok(e) => return *e,
+                    Err(source) => return io::Error::other(source),
+                },
+                None => io::ErrorKind::Other,
+            },
+            ErrorKind::TruncatedInput => io::ErrorKind::UnexpectedEof,
+            ErrorKind::BufferTooSmall => io::ErrorKind::WriteZero,
+            _ => io::ErrorKind::InvalidData,
+        };
+        io::Error::new(kind, e)
+    }
+}
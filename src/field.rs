@@ -0,0 +1,271 @@
+//! Field containers named after the ones in rust-protobuf.
+//!
+//! PROST! uses `Vec<T>` and `Option<T>` directly. These types convert from and
+//! into them, and setters generated by `Classicalize` accept both, so code
+//! written against rust-protobuf compiles with either backend.
+
+use std::iter::FromIterator;
+use std::ops::{Deref, DerefMut};
+use std::{slice, vec};
+
+/// A repeated field, like `protobuf::RepeatedField`.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct RepeatedField<T> {
+    vec: Vec<T>,
+}
+
+impl<T> RepeatedField<T> {
+    /// Create an empty field.
+    pub fn new() -> RepeatedField<T> {
+        RepeatedField { vec: Vec::new() }
+    }
+
+    /// Create an empty field with capacity.
+    pub fn with_capacity(capacity: usize) -> RepeatedField<T> {
+        RepeatedField {
+            vec: Vec::with_capacity(capacity),
+        }
+    }
+
+    /// Create a field that holds the values in vec.
+    pub fn from_vec(vec: Vec<T>) -> RepeatedField<T> {
+        RepeatedField { vec }
+    }
+
+    /// Create a field that holds copies of the values in slice.
+    pub fn from_slice(slice: &[T]) -> RepeatedField<T>
+    where
+        T: Clone,
+    {
+        RepeatedField { vec: slice.to_vec() }
+    }
+
+    /// Convert the field into a vector without copying.
+    pub fn into_vec(self) -> Vec<T> {
+        self.vec
+    }
+
+    /// Copy the values into a vector.
+    pub fn to_vec(&self) -> Vec<T>
+    where
+        T: Clone,
+    {
+        self.vec.clone()
+    }
+
+    pub fn as_slice(&self) -> &[T] {
+        &self.vec
+    }
+
+    pub fn as_mut_slice(&mut self) -> &mut [T] {
+        &mut self.vec
+    }
+
+    pub fn push(&mut self, value: T) {
+        self.vec.push(value);
+    }
+
+    /// Push a default value and get a mutable reference to it.
+    pub fn push_default(&mut self) -> &mut T
+    where
+        T: Default,
+    {
+        self.vec.push(T::default());
+        self.vec.last_mut().unwrap()
+    }
+
+    pub fn pop(&mut self) -> Option<T> {
+        self.vec.pop()
+    }
+
+    pub fn insert(&mut self, index: usize, value: T) {
+        self.vec.insert(index, value);
+    }
+
+    pub fn remove(&mut self, index: usize) -> T {
+        self.vec.remove(index)
+    }
+
+    pub fn truncate(&mut self, len: usize) {
+        self.vec.truncate(len);
+    }
+
+    pub fn clear(&mut self) {
+        self.vec.clear();
+    }
+}
+
+impl<T> Default for RepeatedField<T> {
+    fn default() -> RepeatedField<T> {
+        RepeatedField::new()
+    }
+}
+
+impl<T> Deref for RepeatedField<T> {
+    type Target = [T];
+
+    fn deref(&self) -> &[T] {
+        &self.vec
+    }
+}
+
+impl<T> DerefMut for RepeatedField<T> {
+    fn deref_mut(&mut self) -> &mut [T] {
+        &mut self.vec
+    }
+}
+
+impl<T> AsRef<[T]> for RepeatedField<T> {
+    fn as_ref(&self) -> &[T] {
+        &self.vec
+    }
+}
+
+impl<T> From<Vec<T>> for RepeatedField<T> {
+    fn from(vec: Vec<T>) -> RepeatedField<T> {
+        RepeatedField { vec }
+    }
+}
+
+impl<T> From<RepeatedField<T>> for Vec<T> {
+    fn from(field: RepeatedField<T>) -> Vec<T> {
+        field.vec
+    }
+}
+
+impl<T> FromIterator<T> for RepeatedField<T> {
+    fn from_iter<I: IntoIterator<Item = T>>(iter: I) -> RepeatedField<T> {
+        RepeatedField {
+            vec: iter.into_iter().collect(),
+        }
+    }
+}
+
+impl<T> Extend<T> for RepeatedField<T> {
+    fn extend<I: IntoIterator<Item = T>>(&mut self, iter: I) {
+        self.vec.extend(iter);
+    }
+}
+
+impl<T> IntoIterator for RepeatedField<T> {
+    type Item = T;
+    type IntoIter = vec::IntoIter<T>;
+
+    fn into_iter(self) -> vec::IntoIter<T> {
+        self.vec.into_iter()
+    }
+}
+
+impl<'a, T> IntoIterator for &'a RepeatedField<T> {
+    type Item = &'a T;
+    type IntoIter = slice::Iter<'a, T>;
+
+    fn into_iter(self) -> slice::Iter<'a, T> {
+        self.vec.iter()
+    }
+}
+
+impl<'a, T> IntoIterator for &'a mut RepeatedField<T> {
+    type Item = &'a mut T;
+    type IntoIter = slice::IterMut<'a, T>;
+
+    fn into_iter(self) -> slice::IterMut<'a, T> {
+        self.vec.iter_mut()
+    }
+}
+
+/// An optional message field, like `protobuf::SingularPtrField`.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct SingularPtrField<T> {
+    value: Option<T>,
+}
+
+impl<T> SingularPtrField<T> {
+    /// Create a field that is set to value.
+    pub fn some(value: T) -> SingularPtrField<T> {
+        SingularPtrField { value: Some(value) }
+    }
+
+    /// Create a field that is not set.
+    pub fn none() -> SingularPtrField<T> {
+        SingularPtrField { value: None }
+    }
+
+    pub fn from_option(value: Option<T>) -> SingularPtrField<T> {
+        SingularPtrField { value }
+    }
+
+    pub fn into_option(self) -> Option<T> {
+        self.value
+    }
+
+    pub fn is_some(&self) -> bool {
+        self.value.is_some()
+    }
+
+    pub fn is_none(&self) -> bool {
+        self.value.is_none()
+    }
+
+    pub fn as_ref(&self) -> Option<&T> {
+        self.value.as_ref()
+    }
+
+    pub fn as_mut(&mut self) -> Option<&mut T> {
+        self.value.as_mut()
+    }
+
+    /// Get the value, panics if it's not set.
+    pub fn unwrap(self) -> T {
+        self.value.unwrap()
+    }
+
+    pub fn unwrap_or_default(self) -> T
+    where
+        T: Default,
+    {
+        self.value.unwrap_or_default()
+    }
+
+    /// Set the field to the default value if it's not set, and get a mutable
+    /// reference to the value.
+    pub fn set_default(&mut self) -> &mut T
+    where
+        T: Default,
+    {
+        self.value.get_or_insert_with(T::default)
+    }
+
+    /// Take the value out and leave the field unset.
+    pub fn take(&mut self) -> Option<T> {
+        self.value.take()
+    }
+
+    pub fn clear(&mut self) {
+        self.value = None;
+    }
+}
+
+impl<T> Default for SingularPtrField<T> {
+    fn default() -> SingularPtrField<T> {
+        SingularPtrField::none()
+    }
+}
+
+impl<T> From<T> for SingularPtrField<T> {
+    fn from(value: T) -> SingularPtrField<T> {
+        SingularPtrField::some(value)
+    }
+}
+
+impl<T> From<Option<T>> for SingularPtrField<T> {
+    fn from(value: Option<T>) -> SingularPtrField<T> {
+        SingularPtrField { value }
+    }
+}
+
+impl<T> From<SingularPtrField<T>> for Option<T> {
+    fn from(field: SingularPtrField<T>) -> Option<T> {
+        field.value
+    }
+}
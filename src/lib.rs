@@ -1,6 +1,17 @@
-#[cfg(feature = "prost-codec")]
-#[macro_use]
-extern crate quick_error;
+pub mod compat;
+mod error;
+mod field;
+#[cfg(any(
+    feature = "protobuf-codec",
+    feature = "protobuf3-codec",
+    feature = "prost-codec",
+    feature = "quick-protobuf-codec"
+))]
+mod path;
+
+#[cfg(feature = "bytes")]
+use bytes::{Buf, BufMut, Bytes};
+use std::io::{Read, Write};
 
 /// Unifies different interfaces of message in different protocol implementations.
 pub trait GenericMessage: Sized {
@@ -10,92 +21,1593 @@ pub trait GenericMessage: Sized {
     fn compute_size(&self) -> usize;
     /// Encode the message into buf.
     fn encode_into(&self, buf: &mut Vec<u8>) -> Result<(), Self::Error>;
+    /// Encode the message into buf.
+    ///
+    /// Nothing is written if buf doesn't have enough remaining capacity.
+    /// Every backend enables bytes and writes into buf directly, the default
+    /// is for messages implemented by hand.
+    #[cfg(feature = "bytes")]
+    fn encode_into_buf<B: BufMut>(&self, buf: &mut B) -> Result<(), Self::Error> {
+        if buf.remaining_mut() < self.compute_size() {
+            // Writing to an empty slice fails, so the error is of the message's own type.
+            let mut full: &mut [u8] = &mut [];
+            return self.encode_into_writer(&mut full);
+        }
+        self.encode_into_writer(&mut BufMut::writer(buf))
+    }
+    /// Encode the message and write it to w.
+    fn encode_into_writer<W: Write>(&self, w: &mut W) -> Result<(), Self::Error>;
     /// Decode a message from the data.
     fn decode_from(data: &[u8]) -> Result<Self, Self::Error>;
+    /// Decode a message from all the remaining data in buf.
+    #[cfg(feature = "bytes")]
+    fn decode_from_buf<B: Buf>(buf: B) -> Result<Self, Self::Error> {
+        Self::decode_from_reader(&mut buf.reader())
+    }
+    /// Decode a message from r until EOF.
+    fn decode_from_reader<R: Read>(r: &mut R) -> Result<Self, Self::Error>;
+    /// Merge the encoded message in data into self.
+    ///
+    /// Follows the protobuf merge rules: scalar fields present in data overwrite
+    /// the existing values, repeated fields are appended and sub messages are
+    /// merged recursively.
+    fn merge_from(&mut self, data: &[u8]) -> Result<(), Self::Error>;
+    /// Reset all fields to their default values.
+    ///
+    /// Unlike assigning a new message, backends may keep allocated memory
+    /// for reuse.
+    fn clear(&mut self);
+
+    /// Get the size of encoded messages including the varint length prefix.
+    fn compute_length_delimited_size(&self) -> usize;
+    /// Encode the message into buf, prefixed with its length as a varint.
+    fn encode_length_delimited_into(&self, buf: &mut Vec<u8>) -> Result<(), Self::Error>;
+    /// Decode a length prefixed message from the data.
+    ///
+    /// Bytes after the delimited message are left untouched.
+    fn decode_length_delimited_from(data: &[u8]) -> Result<Self, Self::Error>;
 }
 
+/// Messages whose bytes fields can share the allocation of the input instead
+/// of copying it out.
+///
+/// Only rust-protobuf messages implement it, with `protobuf-with-bytes`. PROST!
+/// 0.5 can't generate `Bytes` fields, so its messages always copy and don't
+/// implement it; neither do the other backends.
+#[cfg(feature = "bytes")]
+pub trait ZeroCopyMessage: GenericMessage {
+    /// Decode a message from the data, fields generated in carllerche bytes
+    /// mode share its allocation.
+    fn decode_from_bytes(data: Bytes) -> Result<Self, Self::Error>;
+}
+
+/// Items used by code generated by `Classicalize`, not part of the public API.
+///
+/// Generated code reaches its dependencies through here, so crates using the
+/// derive only need to depend on jinkela.
+#[doc(hidden)]
+pub mod __private {
+    #[cfg(feature = "bytes")]
+    pub use ::bytes;
+    pub use ::lazy_static;
+    #[cfg(feature = "prost-codec")]
+    pub use ::prost;
+    #[cfg(feature = "protobuf3-codec")]
+    pub use ::protobuf3;
+
+    use std::any::{Any, TypeId};
+    use std::cell::RefCell;
+    use std::collections::HashMap;
+    use std::sync::Mutex;
+
+    type Instances = HashMap<TypeId, &'static (dyn Any + Send + Sync)>;
+
+    /// Gets the default instance of a generic message, which can't have a static of
+    /// its own. Every type gets one instance that lives forever.
+    ///
+    /// Getters of unset generic fields come here, so every thread caches the
+    /// instances it has seen and only takes the global lock the first time.
+    pub fn default_instance<T: Default + Send + Sync + 'static>() -> &'static T {
+        lazy_static::lazy_static! {
+            static ref INSTANCES: Mutex<Instances> = Mutex::default();
+        }
+        thread_local! {
+            static CACHED: RefCell<Instances> = RefCell::default();
+        }
+        let id = TypeId::of::<T>();
+        let instance = CACHED.with(|cached| {
+            *cached.borrow_mut().entry(id).or_insert_with(|| {
+                *INSTANCES
+                    .lock()
+                    .unwrap()
+                    .entry(id)
+                    .or_insert_with(|| Box::leak(Box::new(T::default())))
+            })
+        });
+        instance.downcast_ref().unwrap()
+    }
+}
+
+/// Unifies different interfaces of enumeration in different protocol implementations.
 pub trait GenericEnum: Sized {
+    /// Get all the values defined by the enum.
     fn values() -> &'static [Self];
+    /// Get the numeric value of the enum.
+    fn value(&self) -> i32;
+    /// Get the enum of the numeric value, or `None` if it's not defined.
+    fn from_i32(v: i32) -> Option<Self>;
+    /// Get the name of the value in the proto file.
+    fn name(&self) -> &'static str;
+    /// Get the enum of the name in the proto file, or `None` if it's not defined.
+    fn from_str_name(name: &str) -> Option<Self>;
 }
 
+/// The rust-protobuf backend.
+///
+/// A blanket impl would keep the other backends from implementing the traits
+/// for well-known types, so they are implemented for every type by
+/// `protobuf_message!` and `protobuf_enum!` instead, which jinkela-build
+/// invokes in generated code. Well-known types and descriptors of the file
+/// set are covered here.
 #[cfg(feature = "protobuf-codec")]
-mod codec {
-    pub use protobuf::ProtobufError;
+pub mod protobuf {
+    use crate::error::{ErrorKind, ProtobufError};
+    use crate::path::{self, FieldKind, Segment, Step};
+    use bytes::{Buf, BufMut};
+    use protobuf::descriptor::FieldDescriptorProto_Type;
+    use protobuf::error::WireError;
+    use protobuf::reflect::{FieldDescriptor, MessageDescriptor, ReflectFieldRef, ReflectValueRef};
+    use std::io::{Read, Write};
+
+    impl From<protobuf::ProtobufError> for ProtobufError {
+        fn from(e: protobuf::ProtobufError) -> ProtobufError {
+            let kind = match e {
+                protobuf::ProtobufError::IoError(e) => return e.into(),
+                protobuf::ProtobufError::WireError(ref w) => match w {
+                    WireError::UnexpectedEof | WireError::TruncatedMessage => ErrorKind::TruncatedInput,
+                    WireError::UnexpectedWireType(_) => ErrorKind::InvalidWireType,
+                    WireError::IncorrectTag(_) => ErrorKind::InvalidTag,
+                    WireError::IncorrectVarint => ErrorKind::InvalidVarint,
+                    WireError::Utf8Error => ErrorKind::InvalidUtf8,
+                    WireError::InvalidEnumValue(_) => ErrorKind::UnknownEnumValue,
+                    WireError::OverRecursionLimit => ErrorKind::RecursionLimit,
+                    WireError::IncompleteMap | WireError::Other => ErrorKind::Other,
+                },
+                protobuf::ProtobufError::Utf8(_) => ErrorKind::InvalidUtf8,
+                protobuf::ProtobufError::MessageNotInitialized { .. } => ErrorKind::MissingRequiredField,
+            };
+            ProtobufError::with_source(kind, e)
+        }
+    }
 
-    impl<T: protobuf::Message + Default> super::GenericMessage for T {
-        type Error = ProtobufError;
+    /// Gets the descriptor of the message type of field.
+    ///
+    /// Reflection can't tell the type of an empty repeated field, so a default
+    /// message is merged into the field to get an instance.
+    fn message_descriptor(
+        desc: &'static MessageDescriptor,
+        field: &FieldDescriptor,
+    ) -> Option<&'static MessageDescriptor> {
+        if field.proto().get_field_type() != FieldDescriptorProto_Type::TYPE_MESSAGE {
+            return None;
+        }
+        let mut m = desc.new_instance();
+        m.merge_from_bytes(&path::message_field(field.proto().get_number() as u32, &[])).ok()?;
+        let value = match field.get_reflect(&*m) {
+            ReflectFieldRef::Optional(Some(v)) => v,
+            ReflectFieldRef::Repeated(r) if r.len() > 0 => r.get(0).as_ref(),
+            _ => return None,
+        };
+        match value {
+            ReflectValueRef::Message(m) => Some(m.descriptor()),
+            _ => None,
+        }
+    }
 
-        #[inline]
-        fn compute_size(&self) -> usize {
-            protobuf::Message::compute_size(self) as usize
+    /// Gets the descriptor of the last field in steps and its enclosing message.
+    fn field_at(
+        mut desc: &'static MessageDescriptor,
+        steps: &[Step],
+    ) -> Option<(&'static MessageDescriptor, &'static FieldDescriptor)> {
+        let (last, parents) = steps.split_last()?;
+        for step in parents {
+            desc = message_descriptor(desc, desc.get_field_by_number(step.tag)?)?;
         }
+        desc.get_field_by_number(last.tag).map(|f| (desc, f))
+    }
 
-        #[inline]
-        fn encode_into(&self, buf: &mut Vec<u8>) -> Result<(), ProtobufError> {
-            protobuf::Message::write_to_vec(self, buf)
+    /// Gets the message at the path of field numbers.
+    fn message_at(mut desc: &'static MessageDescriptor, path: &[u32]) -> Option<&'static MessageDescriptor> {
+        for tag in path {
+            desc = message_descriptor(desc, desc.get_field_by_number(*tag)?)?;
         }
+        Some(desc)
+    }
+
+    fn is_singular_message(field: &FieldDescriptor) -> bool {
+        field.proto().get_field_type() == FieldDescriptorProto_Type::TYPE_MESSAGE && !field.is_repeated()
+    }
 
-        #[inline]
-        fn decode_from(data: &[u8]) -> Result<T, ProtobufError> {
-            let mut m = T::default();
-            m.merge_from_bytes(data)?;
-            Ok(m)
+    /// Merges data into m with sub messages merged recursively.
+    ///
+    /// rust-protobuf replaces sub messages instead, so the sub messages that are
+    /// set in m and show up in data are put in front of data, and the occurrences
+    /// of every message field are folded into one before merging.
+    fn merge<T: protobuf::Message>(m: &mut T, data: &[u8]) -> protobuf::ProtobufResult<()> {
+        let root = protobuf::Message::descriptor(m);
+        let mut buf = Vec::new();
+        let mut replaced = false;
+        for (tag, count) in path::tags(data) {
+            let field = match root.get_field_by_number(tag) {
+                Some(field) if is_singular_message(field) => field,
+                _ => continue,
+            };
+            if field.has_field(m) {
+                let existing = field.get_message(m).write_to_bytes()?;
+                buf.extend_from_slice(&path::message_field(tag, &existing));
+                replaced = true;
+            } else if count > 1 {
+                replaced = true;
+            }
+        }
+        // No sub message would be replaced, so rust-protobuf merges it right.
+        if !replaced {
+            return m.merge_from_bytes(data);
+        }
+        buf.extend_from_slice(data);
+        let mut kind = |path: &[u32], tag| match message_at(root, path).and_then(|d| d.get_field_by_number(tag)) {
+            Some(field) => FieldKind {
+                message: is_singular_message(field),
+                oneof: if field.proto().has_oneof_index() {
+                    Some(field.proto().get_oneof_index())
+                } else {
+                    None
+                },
+            },
+            None => FieldKind::default(),
+        };
+        match path::fold(&buf, &mut Vec::new(), &mut kind) {
+            Some(folded) => m.merge_from_bytes(&folded),
+            // Let rust-protobuf report the error.
+            None => m.merge_from_bytes(data),
         }
     }
 
-    impl<T: protobuf::ProtobufEnum> super::GenericEnum for T {
-        #[inline]
-        fn values() -> &'static [Self] {
-            <T as protobuf::ProtobufEnum>::values()
+    fn decode_error<T: protobuf::Message + Default>(e: protobuf::ProtobufError, data: &[u8]) -> ProtobufError {
+        let mut err = ProtobufError::from(e);
+        let root = protobuf::Message::descriptor(&T::default());
+        let steps = path::locate(
+            data,
+            &|d| T::default().merge_from_bytes(d).is_ok(),
+            &mut |steps| match field_at(root, steps) {
+                Some((desc, field)) => message_descriptor(desc, field).is_some(),
+                None => false,
+            },
+        );
+        let mut segments = Vec::with_capacity(steps.len());
+        for i in 0..steps.len() {
+            match field_at(root, &steps[..=i]) {
+                Some((_, field)) => segments.push(Segment {
+                    name: field.name().to_owned(),
+                    indexed: field.is_repeated(),
+                    index: steps[i].index,
+                }),
+                None => break,
+            }
+        }
+        if !segments.is_empty() {
+            err.set_path(path::format(root.name(), &segments));
         }
+        err
+    }
+
+    #[inline]
+    pub fn compute_size<T: protobuf::Message + Default>(msg: &T) -> usize {
+        protobuf::Message::compute_size(msg) as usize
+    }
+
+    #[inline]
+    pub fn encode_into<T: protobuf::Message + Default>(msg: &T, buf: &mut Vec<u8>) -> Result<(), ProtobufError> {
+        protobuf::Message::write_to_vec(msg, buf)?;
+        Ok(())
+    }
+
+    pub fn encode_into_buf<T: protobuf::Message + Default, B: BufMut>(msg: &T, buf: &mut B) -> Result<(), ProtobufError> {
+        let size = protobuf::Message::compute_size(msg) as usize;
+        if buf.remaining_mut() < size {
+            return Err(ProtobufError::new(
+                ErrorKind::BufferTooSmall,
+                format!("{} bytes required but {} remaining", size, buf.remaining_mut()),
+            ));
+        }
+        protobuf::Message::write_to_writer(msg, &mut BufMut::writer(buf))?;
+        Ok(())
+    }
+
+    #[inline]
+    pub fn encode_into_writer<T: protobuf::Message + Default, W: Write>(msg: &T, w: &mut W) -> Result<(), ProtobufError> {
+        protobuf::Message::write_to_writer(msg, w)?;
+        Ok(())
+    }
+
+    #[inline]
+    pub fn decode_from<T: protobuf::Message + Default>(data: &[u8]) -> Result<T, ProtobufError> {
+        let mut m = T::default();
+        m.merge_from_bytes(data).map_err(|e| decode_error::<T>(e, data))?;
+        Ok(m)
+    }
+
+    #[inline]
+    pub fn decode_from_buf<T: protobuf::Message + Default, B: Buf>(buf: B) -> Result<T, ProtobufError> {
+        decode_from_reader(&mut buf.reader())
+    }
+
+    #[inline]
+    pub fn decode_from_reader<T: protobuf::Message + Default, R: Read>(r: &mut R) -> Result<T, ProtobufError> {
+        let mut is = protobuf::CodedInputStream::new(r);
+        let mut m = T::default();
+        protobuf::Message::merge_from(&mut m, &mut is)?;
+        Ok(m)
+    }
+
+    /// rust-protobuf takes bytes 1, which shares the allocation with data.
+    #[cfg(feature = "protobuf-with-bytes")]
+    #[inline]
+    pub fn decode_from_bytes<T: protobuf::Message + Default>(data: bytes::Bytes) -> Result<T, ProtobufError> {
+        let data = bytes1::Bytes::from_owner(data);
+        let mut is = protobuf::CodedInputStream::from_carllerche_bytes(&data);
+        let mut m = T::default();
+        protobuf::Message::merge_from(&mut m, &mut is).map_err(|e| decode_error::<T>(e, &data))?;
+        Ok(m)
+    }
+
+    #[inline]
+    pub fn merge_from<T: protobuf::Message + Default>(msg: &mut T, data: &[u8]) -> Result<(), ProtobufError> {
+        merge(msg, data).map_err(|e| decode_error::<T>(e, data))?;
+        Ok(())
+    }
+
+    #[inline]
+    pub fn clear<T: protobuf::Message + Default>(msg: &mut T) {
+        protobuf::Clear::clear(msg)
+    }
+
+    #[inline]
+    pub fn compute_length_delimited_size<T: protobuf::Message + Default>(msg: &T) -> usize {
+        let size = protobuf::Message::compute_size(msg);
+        protobuf::rt::compute_raw_varint32_size(size) as usize + size as usize
+    }
+
+    #[inline]
+    pub fn encode_length_delimited_into<T: protobuf::Message + Default>(
+        msg: &T,
+        buf: &mut Vec<u8>,
+    ) -> Result<(), ProtobufError> {
+        protobuf::Message::write_length_delimited_to_vec(msg, buf)?;
+        Ok(())
+    }
+
+    #[inline]
+    pub fn decode_length_delimited_from<T: protobuf::Message + Default>(data: &[u8]) -> Result<T, ProtobufError> {
+        let mut is = protobuf::CodedInputStream::from_bytes(data);
+        let mut m = T::default();
+        is.merge_message(&mut m).map_err(|e| match path::delimited(data) {
+            Some(payload) => decode_error::<T>(e, payload),
+            None => e.into(),
+        })?;
+        Ok(m)
+    }
+
+    #[inline]
+    pub fn enum_values<E: protobuf::ProtobufEnum>() -> &'static [E] {
+        E::values()
+    }
+
+    #[inline]
+    pub fn enum_value<E: protobuf::ProtobufEnum>(e: &E) -> i32 {
+        e.value()
+    }
+
+    #[inline]
+    pub fn enum_from_i32<E: protobuf::ProtobufEnum>(v: i32) -> Option<E> {
+        E::from_i32(v)
+    }
+
+    #[inline]
+    pub fn enum_name<E: protobuf::ProtobufEnum>(e: &E) -> &'static str {
+        e.descriptor().name()
+    }
+
+    pub fn enum_from_str_name<E: protobuf::ProtobufEnum>(name: &str) -> Option<E> {
+        E::values().iter().find(|v| v.descriptor().name() == name).cloned()
+    }
+
+    /// Implements `GenericMessage` for rust-protobuf messages, like
+    /// `protobuf_message!(Foo, Foo_Bar);`. With protobuf-with-bytes,
+    /// `ZeroCopyMessage` is implemented too.
+    #[macro_export]
+    macro_rules! protobuf_message {
+        ($($ty:ty),* $(,)*) => {
+            $(
+                impl $crate::GenericMessage for $ty {
+                    type Error = $crate::ProtobufError;
+
+                    #[inline]
+                    fn compute_size(&self) -> usize {
+                        $crate::protobuf::compute_size(self)
+                    }
+
+                    #[inline]
+                    fn encode_into(&self, buf: &mut Vec<u8>) -> ::std::result::Result<(), $crate::ProtobufError> {
+                        $crate::protobuf::encode_into(self, buf)
+                    }
+
+                    #[inline]
+                    fn encode_into_buf<B: $crate::__private::bytes::BufMut>(
+                        &self,
+                        buf: &mut B,
+                    ) -> ::std::result::Result<(), $crate::ProtobufError> {
+                        $crate::protobuf::encode_into_buf(self, buf)
+                    }
+
+                    #[inline]
+                    fn encode_into_writer<W: ::std::io::Write>(&self, w: &mut W) -> ::std::result::Result<(), $crate::ProtobufError> {
+                        $crate::protobuf::encode_into_writer(self, w)
+                    }
+
+                    #[inline]
+                    fn decode_from(data: &[u8]) -> ::std::result::Result<Self, $crate::ProtobufError> {
+                        $crate::protobuf::decode_from(data)
+                    }
+
+                    #[inline]
+                    fn decode_from_buf<B: $crate::__private::bytes::Buf>(buf: B) -> ::std::result::Result<Self, $crate::ProtobufError> {
+                        $crate::protobuf::decode_from_buf(buf)
+                    }
+
+                    #[inline]
+                    fn decode_from_reader<R: ::std::io::Read>(r: &mut R) -> ::std::result::Result<Self, $crate::ProtobufError> {
+                        $crate::protobuf::decode_from_reader(r)
+                    }
+
+                    #[inline]
+                    fn merge_from(&mut self, data: &[u8]) -> ::std::result::Result<(), $crate::ProtobufError> {
+                        $crate::protobuf::merge_from(self, data)
+                    }
+
+                    #[inline]
+                    fn clear(&mut self) {
+                        $crate::protobuf::clear(self)
+                    }
+
+                    #[inline]
+                    fn compute_length_delimited_size(&self) -> usize {
+                        $crate::protobuf::compute_length_delimited_size(self)
+                    }
+
+                    #[inline]
+                    fn encode_length_delimited_into(&self, buf: &mut Vec<u8>) -> ::std::result::Result<(), $crate::ProtobufError> {
+                        $crate::protobuf::encode_length_delimited_into(self, buf)
+                    }
+
+                    #[inline]
+                    fn decode_length_delimited_from(data: &[u8]) -> ::std::result::Result<Self, $crate::ProtobufError> {
+                        $crate::protobuf::decode_length_delimited_from(data)
+                    }
+                }
+
+                $crate::__protobuf_zero_copy!($ty);
+            )*
+        };
+    }
+
+    /// Implements `ZeroCopyMessage` for `protobuf_message!`. It's picked by the
+    /// features of jinkela, which the caller can't see.
+    #[cfg(feature = "protobuf-with-bytes")]
+    #[doc(hidden)]
+    #[macro_export]
+    macro_rules! __protobuf_zero_copy {
+        ($ty:ty) => {
+            impl $crate::ZeroCopyMessage for $ty {
+                #[inline]
+                fn decode_from_bytes(data: $crate::__private::bytes::Bytes) -> ::std::result::Result<Self, $crate::ProtobufError> {
+                    $crate::protobuf::decode_from_bytes(data)
+                }
+            }
+        };
+    }
+
+    #[cfg(not(feature = "protobuf-with-bytes"))]
+    #[doc(hidden)]
+    #[macro_export]
+    macro_rules! __protobuf_zero_copy {
+        ($ty:ty) => {};
+    }
+
+    /// Implements `GenericEnum` for rust-protobuf enums, like
+    /// `protobuf_enum!(Kind, Foo_State);`.
+    #[macro_export]
+    macro_rules! protobuf_enum {
+        ($($ty:ty),* $(,)*) => {
+            $(
+                impl $crate::GenericEnum for $ty {
+                    #[inline]
+                    fn values() -> &'static [Self] {
+                        $crate::protobuf::enum_values()
+                    }
+
+                    #[inline]
+                    fn value(&self) -> i32 {
+                        $crate::protobuf::enum_value(self)
+                    }
+
+                    #[inline]
+                    fn from_i32(v: i32) -> ::std::option::Option<Self> {
+                        $crate::protobuf::enum_from_i32(v)
+                    }
+
+                    #[inline]
+                    fn name(&self) -> &'static str {
+                        $crate::protobuf::enum_name(self)
+                    }
+
+                    #[inline]
+                    fn from_str_name(name: &str) -> ::std::option::Option<Self> {
+                        $crate::protobuf::enum_from_str_name(name)
+                    }
+                }
+            )*
+        };
+    }
+
+    mod well_known_types {
+        use protobuf::descriptor::{FileDescriptorProto, FileDescriptorSet};
+        use protobuf::well_known_types::*;
+
+        protobuf_message!(
+            Any,
+            Api,
+            Method,
+            Mixin,
+            Duration,
+            Empty,
+            FieldMask,
+            SourceContext,
+            Struct,
+            Value,
+            ListValue,
+            Timestamp,
+            Type,
+            Field,
+            Enum,
+            EnumValue,
+            Option,
+            DoubleValue,
+            FloatValue,
+            Int64Value,
+            UInt64Value,
+            Int32Value,
+            UInt32Value,
+            BoolValue,
+            StringValue,
+            BytesValue,
+            FileDescriptorSet,
+            FileDescriptorProto,
+        );
+        protobuf_enum!(NullValue, Syntax, Field_Kind, Field_Cardinality);
     }
 }
 
+/// The PROST! backend.
+///
+/// `Classicalize` implements the traits for every message and enum by
+/// forwarding to the functions here, so any other backend can be enabled at
+/// once. Messages that don't derive `Classicalize` can use `prost_message!`.
+/// Well-known types of prost-types are covered here.
 #[cfg(feature = "prost-codec")]
-mod codec {
-    use prost::{EncodeError, DecodeError};
-
-    quick_error! {
-        /// The error for PROST!. It defines error in a weird way.
-        #[derive(Debug, PartialEq)]
-        pub enum ProtobufError {
-            /// Error for when encoding messages.
-            Encode(err: EncodeError) {
-                from()
-                cause(err)
-                description(err.description())
-                display("{:?}", err)
+pub mod prost {
+    use crate::error::{ErrorKind, ProtobufError};
+    use crate::path::{self, Segment};
+    use bytes::{Buf, BufMut};
+    use prost::{DecodeError, EncodeError, Message};
+    use std::io::{Read, Write};
+
+    impl From<EncodeError> for ProtobufError {
+        fn from(e: EncodeError) -> ProtobufError {
+            // Encoding only fails when running out of capacity.
+            ProtobufError::with_source(ErrorKind::BufferTooSmall, e)
+        }
+    }
+
+    impl From<DecodeError> for ProtobufError {
+        fn from(e: DecodeError) -> ProtobufError {
+            // PROST! doesn't expose the reason, so it has to be guessed from the message.
+            let msg = e.to_string();
+            let kind = if msg.contains("buffer underflow") || msg.contains("delimited length exceeded") {
+                ErrorKind::TruncatedInput
+            } else if msg.contains("invalid varint") {
+                ErrorKind::InvalidVarint
+            } else if msg.contains("wire type") {
+                ErrorKind::InvalidWireType
+            } else if msg.contains("invalid key value") || msg.contains("invalid tag value") {
+                ErrorKind::InvalidTag
+            } else if msg.contains("UTF-8") {
+                ErrorKind::InvalidUtf8
+            } else if msg.contains("recursion limit") {
+                ErrorKind::RecursionLimit
+            } else {
+                ErrorKind::Other
+            };
+            ProtobufError::with_source(kind, e)
+        }
+    }
+
+    /// Gets the (message, field) pairs PROST! records in the error, outermost first.
+    fn decode_stack(e: &DecodeError) -> Vec<(String, String)> {
+        let msg = e.to_string();
+        let mut rest = msg.trim_start_matches("failed to decode Protobuf message: ");
+        let mut stack = Vec::new();
+        while let Some(pos) = rest.find(": ") {
+            let mut parts = rest[..pos].splitn(2, '.');
+            let (message, field) = match (parts.next(), parts.next()) {
+                (Some(m), Some(f)) if is_ident(m) && is_ident(f) => (m, f),
+                _ => break,
+            };
+            stack.push((message.to_owned(), field.trim_start_matches("r#").to_owned()));
+            rest = &rest[pos + 2..];
+        }
+        stack.reverse();
+        stack
+    }
+
+    fn is_ident(s: &str) -> bool {
+        let s = s.trim_start_matches("r#");
+        !s.is_empty()
+            && !s.starts_with(|c: char| c.is_ascii_digit())
+            && s.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+    }
+
+    fn decode_error<T: Message + Default>(e: DecodeError, data: &[u8]) -> ProtobufError {
+        let stack = decode_stack(&e);
+        let mut err = ProtobufError::from(e);
+        // Every entry in the stack is a level of nesting.
+        let levels = stack.len().max(1);
+        let steps = path::locate(data, &|d| T::decode(d).is_ok(), &mut |steps| steps.len() < levels);
+        let segments: Vec<_> = (0..steps.len().max(stack.len()))
+            .map(|i| Segment {
+                name: match stack.get(i) {
+                    Some((_, field)) => field.clone(),
+                    None => format!("#{}", steps[i].tag),
+                },
+                indexed: steps.get(i).is_some_and(|s| s.count > 1),
+                index: steps.get(i).map_or(0, |s| s.index),
+            })
+            .collect();
+        if !segments.is_empty() {
+            let root = match stack.first() {
+                Some((message, _)) => message.as_str(),
+                None => std::any::type_name::<T>().rsplit("::").next().unwrap(),
+            };
+            err.set_path(path::format(root, &segments));
+        }
+        err
+    }
+
+    #[inline]
+    pub fn compute_size<T: Message + Default>(msg: &T) -> usize {
+        msg.encoded_len()
+    }
+
+    #[inline]
+    pub fn encode_into<T: Message + Default>(msg: &T, data: &mut Vec<u8>) -> Result<(), ProtobufError> {
+        msg.encode(data).map_err(ProtobufError::from)
+    }
+
+    #[inline]
+    pub fn encode_into_buf<T: Message + Default, B: BufMut>(msg: &T, buf: &mut B) -> Result<(), ProtobufError> {
+        msg.encode(buf).map_err(ProtobufError::from)
+    }
+
+    /// PROST! only encodes into a `BufMut`, so the encoded message is passed
+    /// to w through a fixed chunk instead of being buffered as a whole.
+    pub fn encode_into_writer<T: Message + Default, W: Write>(msg: &T, w: &mut W) -> Result<(), ProtobufError> {
+        let mut buf = ChunkWriter {
+            w,
+            chunk: [0; CHUNK_SIZE],
+            len: 0,
+            res: Ok(()),
+        };
+        msg.encode(&mut buf)?;
+        buf.flush();
+        buf.res?;
+        Ok(())
+    }
+
+    const CHUNK_SIZE: usize = 4096;
+
+    /// A `BufMut` that writes to w whenever the chunk is full.
+    struct ChunkWriter<'a, W> {
+        w: &'a mut W,
+        chunk: [u8; CHUNK_SIZE],
+        len: usize,
+        // The first error, after which nothing is written.
+        res: std::io::Result<()>,
+    }
+
+    impl<'a, W: Write> ChunkWriter<'a, W> {
+        fn flush(&mut self) {
+            if self.res.is_ok() {
+                self.res = self.w.write_all(&self.chunk[..self.len]);
+            }
+            self.len = 0;
+        }
+    }
+
+    impl<'a, W: Write> BufMut for ChunkWriter<'a, W> {
+        fn remaining_mut(&self) -> usize {
+            usize::MAX
+        }
+
+        unsafe fn advance_mut(&mut self, cnt: usize) {
+            self.len += cnt;
+            if self.len == CHUNK_SIZE {
+                self.flush();
+            }
+        }
+
+        unsafe fn bytes_mut(&mut self) -> &mut [u8] {
+            &mut self.chunk[self.len..]
+        }
+    }
+
+    #[inline]
+    pub fn decode_from<T: Message + Default>(data: &[u8]) -> Result<T, ProtobufError> {
+        T::decode(data).map_err(|e| decode_error::<T>(e, data))
+    }
+
+    /// Errors are located in the data like `decode_from`, which needs a single
+    /// slice, so the path is only reported if buf is contiguous.
+    #[inline]
+    pub fn decode_from_buf<T: Message + Default, B: Buf>(buf: B) -> Result<T, ProtobufError> {
+        if buf.bytes().len() == buf.remaining() {
+            return decode_from(buf.bytes());
+        }
+        T::decode(buf).map_err(ProtobufError::from)
+    }
+
+    pub fn decode_from_reader<T: Message + Default, R: Read>(r: &mut R) -> Result<T, ProtobufError> {
+        let mut data = Vec::new();
+        r.read_to_end(&mut data)?;
+        decode_from(&data)
+    }
+
+    #[inline]
+    pub fn merge_from<T: Message + Default>(msg: &mut T, data: &[u8]) -> Result<(), ProtobufError> {
+        msg.merge(data).map_err(|e| decode_error::<T>(e, data))
+    }
+
+    #[inline]
+    pub fn clear<T: Message + Default>(msg: &mut T) {
+        msg.clear()
+    }
+
+    #[inline]
+    pub fn compute_length_delimited_size<T: Message + Default>(msg: &T) -> usize {
+        let len = msg.encoded_len();
+        prost::length_delimiter_len(len) + len
+    }
+
+    #[inline]
+    pub fn encode_length_delimited_into<T: Message + Default>(msg: &T, data: &mut Vec<u8>) -> Result<(), ProtobufError> {
+        msg.encode_length_delimited(data).map_err(ProtobufError::from)
+    }
+
+    #[inline]
+    pub fn decode_length_delimited_from<T: Message + Default>(data: &[u8]) -> Result<T, ProtobufError> {
+        T::decode_length_delimited(data).map_err(|e| match path::delimited(data) {
+            Some(payload) => decode_error::<T>(e, payload),
+            None => e.into(),
+        })
+    }
+
+    /// Implements `GenericMessage` for PROST! messages that don't derive
+    /// `Classicalize`, like `prost_message!(Foo, foo::Bar);`.
+    #[macro_export]
+    macro_rules! prost_message {
+        ($($ty:ty),* $(,)*) => {
+            $(
+                impl $crate::GenericMessage for $ty {
+                    type Error = $crate::ProtobufError;
+
+                    #[inline]
+                    fn compute_size(&self) -> usize {
+                        $crate::prost::compute_size(self)
+                    }
+
+                    #[inline]
+                    fn encode_into(&self, buf: &mut Vec<u8>) -> ::std::result::Result<(), $crate::ProtobufError> {
+                        $crate::prost::encode_into(self, buf)
+                    }
+
+                    #[inline]
+                    fn encode_into_buf<B: $crate::__private::bytes::BufMut>(
+                        &self,
+                        buf: &mut B,
+                    ) -> ::std::result::Result<(), $crate::ProtobufError> {
+                        $crate::prost::encode_into_buf(self, buf)
+                    }
+
+                    #[inline]
+                    fn encode_into_writer<W: ::std::io::Write>(&self, w: &mut W) -> ::std::result::Result<(), $crate::ProtobufError> {
+                        $crate::prost::encode_into_writer(self, w)
+                    }
+
+                    #[inline]
+                    fn decode_from(data: &[u8]) -> ::std::result::Result<Self, $crate::ProtobufError> {
+                        $crate::prost::decode_from(data)
+                    }
+
+                    #[inline]
+                    fn decode_from_buf<B: $crate::__private::bytes::Buf>(buf: B) -> ::std::result::Result<Self, $crate::ProtobufError> {
+                        $crate::prost::decode_from_buf(buf)
+                    }
+
+                    #[inline]
+                    fn decode_from_reader<R: ::std::io::Read>(r: &mut R) -> ::std::result::Result<Self, $crate::ProtobufError> {
+                        $crate::prost::decode_from_reader(r)
+                    }
+
+                    #[inline]
+                    fn merge_from(&mut self, data: &[u8]) -> ::std::result::Result<(), $crate::ProtobufError> {
+                        $crate::prost::merge_from(self, data)
+                    }
+
+                    #[inline]
+                    fn clear(&mut self) {
+                        $crate::prost::clear(self)
+                    }
+
+                    #[inline]
+                    fn compute_length_delimited_size(&self) -> usize {
+                        $crate::prost::compute_length_delimited_size(self)
+                    }
+
+                    #[inline]
+                    fn encode_length_delimited_into(&self, buf: &mut Vec<u8>) -> ::std::result::Result<(), $crate::ProtobufError> {
+                        $crate::prost::encode_length_delimited_into(self, buf)
+                    }
+
+                    #[inline]
+                    fn decode_length_delimited_from(data: &[u8]) -> ::std::result::Result<Self, $crate::ProtobufError> {
+                        $crate::prost::decode_length_delimited_from(data)
+                    }
+                }
+            )*
+        };
+    }
+
+    mod well_known_types {
+        use prost_types::*;
+
+        prost_message!(
+            Any,
+            Api,
+            Method,
+            Mixin,
+            Duration,
+            FieldMask,
+            SourceContext,
+            Struct,
+            Value,
+            ListValue,
+            Timestamp,
+            Type,
+            Field,
+            Enum,
+            EnumValue,
+            Option,
+            FileDescriptorSet,
+            FileDescriptorProto,
+        );
+    }
+}
+
+/// The quick-protobuf backend.
+///
+/// Messages generated by pb-rs may borrow from the input, which the traits
+/// can't express, so `GenericMessage` is only implemented for messages that
+/// own their fields. Borrowed messages can still be decoded by
+/// `decode_borrowed` without copying.
+///
+/// The traits are implemented for every type by `quick_protobuf_message!` and
+/// `quick_protobuf_enum!`, which jinkela-build invokes in generated code.
+#[cfg(feature = "quick-protobuf-codec")]
+pub mod quick_protobuf {
+    use crate::error::{ErrorKind, ProtobufError};
+    use crate::path::{self, FieldKind, Segment};
+    use bytes::{Buf, BufMut};
+    use quick_protobuf::{BytesReader, MessageRead, MessageWrite, Writer};
+    use std::io::{Read, Write};
+
+    impl From<quick_protobuf::Error> for ProtobufError {
+        fn from(e: quick_protobuf::Error) -> ProtobufError {
+            let kind = match e {
+                quick_protobuf::Error::Io(e) => return e.into(),
+                quick_protobuf::Error::Utf8(_) => ErrorKind::InvalidUtf8,
+                quick_protobuf::Error::UnknownWireType(_) => ErrorKind::InvalidWireType,
+                quick_protobuf::Error::Varint => ErrorKind::InvalidVarint,
+                quick_protobuf::Error::UnexpectedEndOfBuffer => ErrorKind::TruncatedInput,
+                quick_protobuf::Error::OutputBufferTooSmall => ErrorKind::BufferTooSmall,
+                _ => ErrorKind::Other,
+            };
+            ProtobufError::with_source(kind, e)
+        }
+    }
+
+    fn read<'a, M: MessageRead<'a>>(data: &'a [u8]) -> quick_protobuf::Result<M> {
+        let mut reader = BytesReader::from_bytes(data);
+        M::from_reader(&mut reader, data)
+    }
+
+    fn decode_error<M: for<'a> MessageRead<'a>>(e: quick_protobuf::Error, data: &[u8]) -> ProtobufError {
+        let mut err = ProtobufError::from(e);
+        // pb-rs doesn't keep field names, so fields are named by numbers.
+        let steps = path::locate(data, &|d| read::<M>(d).is_ok(), &mut |_| true);
+        if !steps.is_empty() {
+            let segments: Vec<_> = steps
+                .iter()
+                .map(|s| Segment {
+                    name: format!("#{}", s.tag),
+                    indexed: s.count > 1,
+                    index: s.index,
+                })
+                .collect();
+            let root = std::any::type_name::<M>().rsplit("::").next().unwrap();
+            err.set_path(path::format(root, &segments));
+        }
+        err
+    }
+
+    /// Decode a message that may borrow from data.
+    pub fn decode_borrowed<'a, M: MessageRead<'a>>(data: &'a [u8]) -> Result<M, ProtobufError> {
+        read(data).map_err(ProtobufError::from)
+    }
+
+    #[inline]
+    pub fn compute_size<M: MessageWrite>(msg: &M) -> usize {
+        msg.get_size()
+    }
+
+    #[inline]
+    pub fn encode_into<M: MessageWrite>(msg: &M, data: &mut Vec<u8>) -> Result<(), ProtobufError> {
+        let mut writer = Writer::new(data);
+        msg.write_message(&mut writer).map_err(ProtobufError::from)
+    }
+
+    pub fn encode_into_buf<M: MessageWrite, B: BufMut>(msg: &M, buf: &mut B) -> Result<(), ProtobufError> {
+        let size = msg.get_size();
+        if buf.remaining_mut() < size {
+            let e = format!("{} bytes required but only {} remaining", size, buf.remaining_mut());
+            return Err(ProtobufError::new(ErrorKind::BufferTooSmall, e));
+        }
+        let mut writer = Writer::new(BufMut::writer(buf));
+        msg.write_message(&mut writer).map_err(ProtobufError::from)
+    }
+
+    /// quick-protobuf writes every field to w as it goes, so w should be buffered.
+    #[inline]
+    pub fn encode_into_writer<M: MessageWrite, W: Write>(msg: &M, w: &mut W) -> Result<(), ProtobufError> {
+        let mut writer = Writer::new(w);
+        msg.write_message(&mut writer).map_err(ProtobufError::from)
+    }
+
+    #[inline]
+    pub fn decode_from<M: for<'a> MessageRead<'a>>(data: &[u8]) -> Result<M, ProtobufError> {
+        read(data).map_err(|e| decode_error::<M>(e, data))
+    }
+
+    pub fn decode_from_buf<M: for<'a> MessageRead<'a>, B: Buf>(mut buf: B) -> Result<M, ProtobufError> {
+        let mut data = Vec::with_capacity(buf.remaining());
+        while buf.has_remaining() {
+            let len = buf.bytes().len();
+            data.extend_from_slice(buf.bytes());
+            buf.advance(len);
+        }
+        decode_from(&data)
+    }
+
+    pub fn decode_from_reader<M: for<'a> MessageRead<'a>, R: Read>(r: &mut R) -> Result<M, ProtobufError> {
+        let mut data = Vec::new();
+        r.read_to_end(&mut data)?;
+        decode_from(&data)
+    }
+
+    /// A field pb-rs can't merge by itself, which is either a singular message
+    /// field or a member of a oneof.
+    #[derive(Clone, Copy, Debug)]
+    pub struct MergeField {
+        /// The field number.
+        pub tag: u32,
+        /// The index of the oneof it belongs to.
+        pub oneof: Option<i32>,
+        /// The full name of the message type if it's a singular message field.
+        pub message: Option<&'static str>,
+    }
+
+    /// Gets how the field is declared by the message at the path of field
+    /// numbers. The first of messages is the root.
+    fn field_kind(messages: &[(&str, &[MergeField])], path: &[u32], tag: u32) -> FieldKind {
+        let fields = |name: &str| messages.iter().find(|(n, _)| *n == name).map_or(&[][..], |(_, f)| *f);
+        let mut name = match messages.first() {
+            Some((name, _)) => *name,
+            None => return FieldKind::default(),
+        };
+        for t in path {
+            match fields(name).iter().find(|f| f.tag == *t).and_then(|f| f.message) {
+                Some(message) => name = message,
+                None => return FieldKind::default(),
             }
-            /// Error for decoding messages.
-            Decode(err: DecodeError) {
-                from()
-                cause(err)
-                description(err.description())
-                display("{:?}", err)
+        }
+        match fields(name).iter().find(|f| f.tag == tag) {
+            Some(f) => FieldKind { message: f.message.is_some(), oneof: f.oneof },
+            None => FieldKind::default(),
+        }
+    }
+
+    /// quick-protobuf can't merge, so data is decoded after the encoded msg.
+    /// pb-rs replaces sub messages that show up again, so the occurrences of
+    /// every message field are folded into one first. messages are the fields
+    /// to fold of every message reachable from msg, msg first.
+    pub fn merge_from<M: MessageWrite + for<'a> MessageRead<'a>>(
+        msg: &mut M,
+        data: &[u8],
+        messages: &[(&str, &[MergeField])],
+    ) -> Result<(), ProtobufError> {
+        let mut merged = Vec::with_capacity(msg.get_size() + data.len());
+        encode_into(msg, &mut merged)?;
+        merged.extend_from_slice(data);
+        if !messages.is_empty() {
+            let mut kind = |path: &[u32], tag| field_kind(messages, path, tag);
+            // Let pb-rs report the error if data is malformed.
+            if let Some(folded) = path::fold(&merged, &mut Vec::new(), &mut kind) {
+                merged = folded;
+            }
+        }
+        match read(&merged) {
+            Ok(m) => {
+                *msg = m;
+                Ok(())
             }
+            Err(e) => Err(decode_error::<M>(e, data)),
+        }
+    }
+
+    #[inline]
+    pub fn clear<M: Default>(msg: &mut M) {
+        *msg = M::default();
+    }
+
+    #[inline]
+    pub fn compute_length_delimited_size<M: MessageWrite>(msg: &M) -> usize {
+        quick_protobuf::sizeofs::sizeof_len(msg.get_size())
+    }
+
+    #[inline]
+    pub fn encode_length_delimited_into<M: MessageWrite>(msg: &M, data: &mut Vec<u8>) -> Result<(), ProtobufError> {
+        let mut writer = Writer::new(data);
+        writer.write_message(msg).map_err(ProtobufError::from)
+    }
+
+    #[inline]
+    pub fn decode_length_delimited_from<M: for<'a> MessageRead<'a>>(data: &[u8]) -> Result<M, ProtobufError> {
+        let mut reader = BytesReader::from_bytes(data);
+        reader.read_message(data).map_err(|e| match path::delimited(data) {
+            Some(payload) => decode_error::<M>(e, payload),
+            None => e.into(),
+        })
+    }
+
+    /// Implements `GenericMessage` for quick-protobuf messages that own their
+    /// fields, like
+    ///
+    /// ```text
+    /// quick_protobuf_message!(
+    ///     Foo { ".pkg.Foo" { 1 => ".pkg.Bar", 2 in 0, 3 in 0 => ".pkg.Bar" } },
+    ///     mod_Foo::Bar {},
+    /// );
+    /// ```
+    ///
+    /// The braces list the fields `merge_from` has to fold by the full names of
+    /// the messages reachable from the type, the type itself first: singular
+    /// message fields with the name of their type after `=>`, and members of
+    /// oneofs with the index of the oneof after `in`. They are empty if the
+    /// type has no such fields.
+    #[macro_export]
+    macro_rules! quick_protobuf_message {
+        ($($ty:ty {
+            $($name:literal { $($tag:literal $(in $oneof:literal)? $(=> $message:literal)?),* $(,)* }),* $(,)*
+        }),* $(,)*) => {
+            $(
+                impl $crate::GenericMessage for $ty {
+                    type Error = $crate::ProtobufError;
+
+                    #[inline]
+                    fn compute_size(&self) -> usize {
+                        $crate::quick_protobuf::compute_size(self)
+                    }
+
+                    #[inline]
+                    fn encode_into(&self, buf: &mut Vec<u8>) -> ::std::result::Result<(), $crate::ProtobufError> {
+                        $crate::quick_protobuf::encode_into(self, buf)
+                    }
+
+                    #[inline]
+                    fn encode_into_buf<B: $crate::__private::bytes::BufMut>(
+                        &self,
+                        buf: &mut B,
+                    ) -> ::std::result::Result<(), $crate::ProtobufError> {
+                        $crate::quick_protobuf::encode_into_buf(self, buf)
+                    }
+
+                    #[inline]
+                    fn encode_into_writer<W: ::std::io::Write>(&self, w: &mut W) -> ::std::result::Result<(), $crate::ProtobufError> {
+                        $crate::quick_protobuf::encode_into_writer(self, w)
+                    }
+
+                    #[inline]
+                    fn decode_from(data: &[u8]) -> ::std::result::Result<Self, $crate::ProtobufError> {
+                        $crate::quick_protobuf::decode_from(data)
+                    }
+
+                    #[inline]
+                    fn decode_from_buf<B: $crate::__private::bytes::Buf>(buf: B) -> ::std::result::Result<Self, $crate::ProtobufError> {
+                        $crate::quick_protobuf::decode_from_buf(buf)
+                    }
+
+                    #[inline]
+                    fn decode_from_reader<R: ::std::io::Read>(r: &mut R) -> ::std::result::Result<Self, $crate::ProtobufError> {
+                        $crate::quick_protobuf::decode_from_reader(r)
+                    }
+
+                    #[inline]
+                    fn merge_from(&mut self, data: &[u8]) -> ::std::result::Result<(), $crate::ProtobufError> {
+                        $crate::quick_protobuf::merge_from(self, data, &[$(
+                            ($name, &[$(
+                                $crate::quick_protobuf::MergeField {
+                                    tag: $tag,
+                                    oneof: $crate::__quick_protobuf_some!($($oneof)?),
+                                    message: $crate::__quick_protobuf_some!($($message)?),
+                                }
+                            ),*] as &[_]),
+                        )*])
+                    }
+
+                    #[inline]
+                    fn clear(&mut self) {
+                        $crate::quick_protobuf::clear(self)
+                    }
+
+                    #[inline]
+                    fn compute_length_delimited_size(&self) -> usize {
+                        $crate::quick_protobuf::compute_length_delimited_size(self)
+                    }
+
+                    #[inline]
+                    fn encode_length_delimited_into(&self, buf: &mut Vec<u8>) -> ::std::result::Result<(), $crate::ProtobufError> {
+                        $crate::quick_protobuf::encode_length_delimited_into(self, buf)
+                    }
+
+                    #[inline]
+                    fn decode_length_delimited_from(data: &[u8]) -> ::std::result::Result<Self, $crate::ProtobufError> {
+                        $crate::quick_protobuf::decode_length_delimited_from(data)
+                    }
+                }
+            )*
+        };
+    }
+
+    #[doc(hidden)]
+    #[macro_export]
+    macro_rules! __quick_protobuf_some {
+        () => {
+            ::std::option::Option::None
+        };
+        ($value:expr) => {
+            ::std::option::Option::Some($value)
+        };
+    }
+
+    /// Implements `GenericEnum` for enums generated by pb-rs, which keep the
+    /// names in proto files, like `quick_protobuf_enum!(Kind { A, B })`.
+    #[macro_export]
+    macro_rules! quick_protobuf_enum {
+        ($($ty:path { $($variant:ident),* $(,)* })*) => {
+            $(
+                impl $crate::GenericEnum for $ty {
+                    fn values() -> &'static [Self] {
+                        &[$(Self::$variant),*]
+                    }
+
+                    fn value(&self) -> i32 {
+                        *self as i32
+                    }
+
+                    fn from_i32(v: i32) -> ::std::option::Option<Self> {
+                        $(
+                            if v == Self::$variant as i32 {
+                                return ::std::option::Option::Some(Self::$variant);
+                            }
+                        )*
+                        ::std::option::Option::None
+                    }
+
+                    fn name(&self) -> &'static str {
+                        match *self {
+                            $(Self::$variant => stringify!($variant),)*
+                        }
+                    }
+
+                    fn from_str_name(name: &str) -> ::std::option::Option<Self> {
+                        match name {
+                            $(stringify!($variant) => ::std::option::Option::Some(Self::$variant),)*
+                            _ => ::std::option::Option::None,
+                        }
+                    }
+                }
+            )*
+        };
+    }
+}
+
+/// The rust-protobuf 3 backend, which can be enabled together with the one of
+/// rust-protobuf 2.
+///
+/// Both versions would need a blanket impl, so the traits are implemented for
+/// every type by `protobuf3_message!` and `protobuf3_enum!` instead. Generated
+/// code invokes them by deriving `Protobuf3`. Well-known types are covered
+/// here.
+#[cfg(feature = "protobuf3-codec")]
+pub mod protobuf3 {
+    use crate::error::{ErrorKind, ProtobufError};
+    use crate::path::{self, Segment, Step};
+    use bytes::{Buf, BufMut};
+    use protobuf3::reflect::{FieldDescriptor, MessageDescriptor, RuntimeFieldType, RuntimeType};
+    use protobuf3::{CodedInputStream, EnumFull, Message, MessageFull};
+    use std::collections::HashSet;
+    use std::io::{self, Read, Write};
+    use std::sync::Mutex;
+
+    impl From<protobuf3::Error> for ProtobufError {
+        fn from(e: protobuf3::Error) -> ProtobufError {
+            // rust-protobuf 3 keeps the reasons crate private, but their
+            // variants show up in `Debug`, like `Error(WireError(UnexpectedEof))`.
+            // The minor version is pinned so tests catch changes to them.
+            let debug = format!("{:?}", e);
+            let reason = debug.strip_prefix("Error(").unwrap_or(&debug);
+            let kind = if reason.starts_with("IoError(") {
+                return io::Error::from(e).into();
+            } else if let Some(wire) = reason.strip_prefix("WireError(") {
+                let wire = wire.split(|c: char| !c.is_ascii_alphanumeric()).next().unwrap_or_default();
+                match wire {
+                    "UnexpectedEof" | "TruncatedMessage" => ErrorKind::TruncatedInput,
+                    "UnexpectedWireType" => ErrorKind::InvalidWireType,
+                    "IncorrectTag" => ErrorKind::InvalidTag,
+                    "IncorrectVarint" => ErrorKind::InvalidVarint,
+                    "Utf8Error" => ErrorKind::InvalidUtf8,
+                    "OverRecursionLimit" => ErrorKind::RecursionLimit,
+                    _ => ErrorKind::Other,
+                }
+            } else if reason.starts_with("MessageNotInitialized(") {
+                ErrorKind::MissingRequiredField
+            } else if reason.starts_with("BufferHasNotEnoughCapacity(") {
+                ErrorKind::BufferTooSmall
+            } else {
+                ErrorKind::Other
+            };
+            ProtobufError::with_source(kind, e)
         }
     }
 
-    impl<T: prost::Message + Default> super::GenericMessage for T {
-        type Error = ProtobufError;
+    /// Gets the message type of field if it holds messages.
+    fn message_type(field: &FieldDescriptor) -> Option<MessageDescriptor> {
+        match field.runtime_field_type() {
+            RuntimeFieldType::Singular(RuntimeType::Message(m))
+            | RuntimeFieldType::Repeated(RuntimeType::Message(m)) => Some(m),
+            _ => None,
+        }
+    }
 
-        #[inline]
-        fn compute_size(&self) -> usize {
-            self.encoded_len()
+    /// Gets the descriptor of the last field in steps.
+    fn field_at(mut desc: MessageDescriptor, steps: &[Step]) -> Option<FieldDescriptor> {
+        let (last, parents) = steps.split_last()?;
+        for step in parents {
+            desc = message_type(&desc.field_by_number(step.tag)?)?;
         }
+        desc.field_by_number(last.tag)
+    }
 
-        #[inline]
-        fn encode_into(&self, data: &mut Vec<u8>) -> Result<(), ProtobufError> {
-            prost::Message::encode(self, data).map_err(ProtobufError::Encode)
+    fn decode_error<M: MessageFull>(e: protobuf3::Error, data: &[u8]) -> ProtobufError {
+        let mut err = ProtobufError::from(e);
+        let root = M::descriptor();
+        let steps = path::locate(
+            data,
+            &|d| M::new().merge_from_bytes(d).is_ok(),
+            &mut |steps| field_at(root.clone(), steps).and_then(|f| message_type(&f)).is_some(),
+        );
+        let mut segments = Vec::with_capacity(steps.len());
+        for i in 0..steps.len() {
+            match field_at(root.clone(), &steps[..=i]) {
+                Some(field) => segments.push(Segment {
+                    name: field.name().to_owned(),
+                    indexed: field.is_repeated(),
+                    index: steps[i].index,
+                }),
+                None => break,
+            }
+        }
+        if !segments.is_empty() {
+            err.set_path(path::format(root.name(), &segments));
         }
+        err
+    }
+
+    #[inline]
+    pub fn compute_size<M: Message>(msg: &M) -> usize {
+        msg.compute_size() as usize
+    }
 
-        #[inline]
-        fn decode_from(data: &[u8]) -> Result<T, ProtobufError> {
-            T::decode(data).map_err(ProtobufError::Decode)
+    #[inline]
+    pub fn encode_into<M: Message>(msg: &M, buf: &mut Vec<u8>) -> Result<(), ProtobufError> {
+        msg.write_to_vec(buf)?;
+        Ok(())
+    }
+
+    pub fn encode_into_buf<M: Message, B: BufMut>(msg: &M, buf: &mut B) -> Result<(), ProtobufError> {
+        let size = msg.compute_size() as usize;
+        if buf.remaining_mut() < size {
+            return Err(ProtobufError::new(
+                ErrorKind::BufferTooSmall,
+                format!("{} bytes required but {} remaining", size, buf.remaining_mut()),
+            ));
         }
+        msg.write_to_writer(&mut BufMut::writer(buf))?;
+        Ok(())
+    }
+
+    #[inline]
+    pub fn encode_into_writer<M: Message, W: Write>(msg: &M, w: &mut W) -> Result<(), ProtobufError> {
+        msg.write_to_writer(w)?;
+        Ok(())
+    }
+
+    #[inline]
+    pub fn decode_from<M: MessageFull>(data: &[u8]) -> Result<M, ProtobufError> {
+        let mut m = M::new();
+        m.merge_from_bytes(data).map_err(|e| decode_error::<M>(e, data))?;
+        Ok(m)
+    }
+
+    #[inline]
+    pub fn decode_from_buf<M: MessageFull, B: Buf>(buf: B) -> Result<M, ProtobufError> {
+        decode_from_reader(&mut buf.reader())
+    }
+
+    #[inline]
+    pub fn decode_from_reader<M: MessageFull, R: Read>(r: &mut R) -> Result<M, ProtobufError> {
+        let mut is = CodedInputStream::new(r);
+        let mut m = M::new();
+        m.merge_from(&mut is)?;
+        Ok(m)
+    }
+
+    #[inline]
+    pub fn merge_from<M: MessageFull>(msg: &mut M, data: &[u8]) -> Result<(), ProtobufError> {
+        msg.merge_from_bytes(data).map_err(|e| decode_error::<M>(e, data))?;
+        Ok(())
+    }
+
+    #[inline]
+    pub fn clear<M: Message>(msg: &mut M) {
+        msg.clear()
+    }
+
+    #[inline]
+    pub fn compute_length_delimited_size<M: Message>(msg: &M) -> usize {
+        let size = msg.compute_size();
+        (protobuf3::rt::compute_raw_varint64_size(size) + size) as usize
+    }
+
+    #[inline]
+    pub fn encode_length_delimited_into<M: Message>(msg: &M, buf: &mut Vec<u8>) -> Result<(), ProtobufError> {
+        msg.write_length_delimited_to_vec(buf)?;
+        Ok(())
+    }
+
+    #[inline]
+    pub fn decode_length_delimited_from<M: MessageFull>(data: &[u8]) -> Result<M, ProtobufError> {
+        let mut is = CodedInputStream::from_bytes(data);
+        let mut m = M::new();
+        is.merge_message(&mut m).map_err(|e| match path::delimited(data) {
+            Some(payload) => decode_error::<M>(e, payload),
+            None => e.into(),
+        })?;
+        Ok(m)
+    }
+
+    /// Names only live as long as the descriptor they are read from, so they
+    /// are leaked once to be returned as `&'static str`.
+    fn intern(name: &str) -> &'static str {
+        lazy_static::lazy_static! {
+            static ref NAMES: Mutex<HashSet<&'static str>> = Mutex::default();
+        }
+        let mut names = NAMES.lock().unwrap();
+        if let Some(name) = names.get(name) {
+            return name;
+        }
+        let name: &'static str = Box::leak(name.to_owned().into_boxed_str());
+        names.insert(name);
+        name
+    }
+
+    #[inline]
+    pub fn enum_name<E: EnumFull>(e: &E) -> &'static str {
+        intern(e.descriptor().name())
+    }
+
+    pub fn enum_from_str_name<E: EnumFull>(name: &str) -> Option<E> {
+        E::VALUES.iter().find(|v| v.descriptor().name() == name).cloned()
+    }
+
+    /// Implements `GenericMessage` for rust-protobuf 3 messages, like
+    /// `protobuf3_message!(Foo, foo::Bar);`.
+    #[macro_export]
+    macro_rules! protobuf3_message {
+        ($($ty:ty),* $(,)*) => {
+            $(
+                impl $crate::GenericMessage for $ty {
+                    type Error = $crate::ProtobufError;
+
+                    #[inline]
+                    fn compute_size(&self) -> usize {
+                        $crate::protobuf3::compute_size(self)
+                    }
+
+                    #[inline]
+                    fn encode_into(&self, buf: &mut Vec<u8>) -> ::std::result::Result<(), $crate::ProtobufError> {
+                        $crate::protobuf3::encode_into(self, buf)
+                    }
+
+                    #[inline]
+                    fn encode_into_buf<B: $crate::__private::bytes::BufMut>(
+                        &self,
+                        buf: &mut B,
+                    ) -> ::std::result::Result<(), $crate::ProtobufError> {
+                        $crate::protobuf3::encode_into_buf(self, buf)
+                    }
+
+                    #[inline]
+                    fn encode_into_writer<W: ::std::io::Write>(&self, w: &mut W) -> ::std::result::Result<(), $crate::ProtobufError> {
+                        $crate::protobuf3::encode_into_writer(self, w)
+                    }
+
+                    #[inline]
+                    fn decode_from(data: &[u8]) -> ::std::result::Result<Self, $crate::ProtobufError> {
+                        $crate::protobuf3::decode_from(data)
+                    }
+
+                    #[inline]
+                    fn decode_from_buf<B: $crate::__private::bytes::Buf>(buf: B) -> ::std::result::Result<Self, $crate::ProtobufError> {
+                        $crate::protobuf3::decode_from_buf(buf)
+                    }
+
+                    #[inline]
+                    fn decode_from_reader<R: ::std::io::Read>(r: &mut R) -> ::std::result::Result<Self, $crate::ProtobufError> {
+                        $crate::protobuf3::decode_from_reader(r)
+                    }
+
+                    #[inline]
+                    fn merge_from(&mut self, data: &[u8]) -> ::std::result::Result<(), $crate::ProtobufError> {
+                        $crate::protobuf3::merge_from(self, data)
+                    }
+
+                    #[inline]
+                    fn clear(&mut self) {
+                        $crate::protobuf3::clear(self)
+                    }
+
+                    #[inline]
+                    fn compute_length_delimited_size(&self) -> usize {
+                        $crate::protobuf3::compute_length_delimited_size(self)
+                    }
+
+                    #[inline]
+                    fn encode_length_delimited_into(&self, buf: &mut Vec<u8>) -> ::std::result::Result<(), $crate::ProtobufError> {
+                        $crate::protobuf3::encode_length_delimited_into(self, buf)
+                    }
+
+                    #[inline]
+                    fn decode_length_delimited_from(data: &[u8]) -> ::std::result::Result<Self, $crate::ProtobufError> {
+                        $crate::protobuf3::decode_length_delimited_from(data)
+                    }
+                }
+            )*
+        };
+    }
+
+    /// Implements `GenericEnum` for rust-protobuf 3 enums, like
+    /// `protobuf3_enum!(Kind, foo::State);`.
+    #[macro_export]
+    macro_rules! protobuf3_enum {
+        ($($ty:ty),* $(,)*) => {
+            $(
+                impl $crate::GenericEnum for $ty {
+                    #[inline]
+                    fn values() -> &'static [Self] {
+                        <Self as $crate::__private::protobuf3::Enum>::VALUES
+                    }
+
+                    #[inline]
+                    fn value(&self) -> i32 {
+                        $crate::__private::protobuf3::Enum::value(self)
+                    }
+
+                    #[inline]
+                    fn from_i32(v: i32) -> ::std::option::Option<Self> {
+                        <Self as $crate::__private::protobuf3::Enum>::from_i32(v)
+                    }
+
+                    #[inline]
+                    fn name(&self) -> &'static str {
+                        $crate::protobuf3::enum_name(self)
+                    }
+
+                    #[inline]
+                    fn from_str_name(name: &str) -> ::std::option::Option<Self> {
+                        $crate::protobuf3::enum_from_str_name(name)
+                    }
+                }
+            )*
+        };
+    }
+
+    mod well_known_types {
+        use protobuf3::well_known_types::*;
+
+        protobuf3_message!(
+            any::Any,
+            api::Api,
+            api::Method,
+            api::Mixin,
+            duration::Duration,
+            empty::Empty,
+            field_mask::FieldMask,
+            source_context::SourceContext,
+            struct_::Struct,
+            struct_::Value,
+            struct_::ListValue,
+            timestamp::Timestamp,
+            type_::Type,
+            type_::Field,
+            type_::Enum,
+            type_::EnumValue,
+            type_::Option,
+            wrappers::DoubleValue,
+            wrappers::FloatValue,
+            wrappers::Int64Value,
+            wrappers::UInt64Value,
+            wrappers::Int32Value,
+            wrappers::UInt32Value,
+            wrappers::BoolValue,
+            wrappers::StringValue,
+            wrappers::BytesValue,
+        );
+        protobuf3_enum!(struct_::NullValue, type_::Syntax, type_::field::Kind, type_::field::Cardinality);
     }
 }
 
-pub use codec::ProtobufError;
+pub use error::{ErrorKind, ProtobufError};
+pub use field::{RepeatedField, SingularPtrField};
 #[cfg(feature = "prost-codec")]
-pub use jinkela_derive::*;
+pub use jinkela_derive::Classicalize;
+#[cfg(feature = "protobuf3-codec")]
+pub use jinkela_derive::Protobuf3;
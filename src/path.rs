@@ -0,0 +1,291 @@
+//! Locates the field that makes a message fail to decode.
+//!
+//! Backends only report what went wrong, so the location is found by walking
+//! the wire format and decoding every field in isolation. A field that can't be
+//! decoded alone is the culprit, and if it's a nested message the search
+//! continues inside it with the field wrapped into its ancestors.
+//!
+//! rust-protobuf 2 and quick-protobuf also rely on the wire format here to
+//! merge messages.
+
+use std::collections::HashMap;
+
+/// A field on the path to the failing field.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) struct Step {
+    /// The field number.
+    pub tag: u32,
+    /// The position of the field among the fields with the same number.
+    pub index: usize,
+    /// How many times the field number shows up in the enclosing message.
+    pub count: usize,
+}
+
+/// A field that can be named in a path.
+pub(crate) struct Segment {
+    pub name: String,
+    /// Whether the index should be shown.
+    pub indexed: bool,
+    pub index: usize,
+}
+
+fn read_varint(data: &mut &[u8]) -> Option<u64> {
+    let mut value = 0;
+    for i in 0..10 {
+        let b = *data.get(i)?;
+        value |= u64::from(b & 0x7f) << (7 * i);
+        if b < 0x80 {
+            *data = &data[i + 1..];
+            return Some(value);
+        }
+    }
+    None
+}
+
+fn write_varint(buf: &mut Vec<u8>, mut value: u64) {
+    while value >= 0x80 {
+        buf.push(value as u8 | 0x80);
+        value >>= 7;
+    }
+    buf.push(value as u8);
+}
+
+fn skip(data: &mut &[u8], len: u64) -> Option<()> {
+    if (data.len() as u64) < len {
+        return None;
+    }
+    *data = &data[len as usize..];
+    Some(())
+}
+
+fn skip_group(data: &mut &[u8], tag: u32) -> Option<()> {
+    loop {
+        let mut peek = *data;
+        let key = read_varint(&mut peek)?;
+        if key & 7 == 4 {
+            *data = peek;
+            return if key >> 3 == u64::from(tag) { Some(()) } else { None };
+        }
+        next_field(data).ok()?;
+    }
+}
+
+/// A field number, its raw bytes and payload if it's length delimited.
+type RawField<'a> = (u32, &'a [u8], Option<&'a [u8]>);
+
+/// Reads the next field.
+///
+/// If the field is malformed, its number is returned as error when known.
+fn next_field<'a>(data: &mut &'a [u8]) -> Result<RawField<'a>, Option<u32>> {
+    let start = *data;
+    let key = read_varint(data).ok_or(None)?;
+    if key >> 3 == 0 || key >> 3 > u64::from(u32::MAX >> 3) {
+        return Err(None);
+    }
+    let tag = (key >> 3) as u32;
+    let payload = match key & 7 {
+        0 => read_varint(data).map(|_| None),
+        1 => skip(data, 8).map(|_| None),
+        2 => read_varint(data).and_then(|len| {
+            let payload = *data;
+            skip(data, len).map(|_| Some(&payload[..len as usize]))
+        }),
+        3 => skip_group(data, tag).map(|_| None),
+        5 => skip(data, 4).map(|_| None),
+        _ => None,
+    };
+    match payload {
+        Some(payload) => Ok((tag, &start[..start.len() - data.len()], payload)),
+        None => Err(Some(tag)),
+    }
+}
+
+/// Gets the payload of a length delimited message, which may be truncated.
+pub(crate) fn delimited(mut data: &[u8]) -> Option<&[u8]> {
+    let len = read_varint(&mut data)?;
+    Some(&data[..data.len().min(len as usize)])
+}
+
+/// Wraps the raw field into the length delimited fields of ancestors.
+fn wrap(ancestors: &[Step], raw: &[u8]) -> Vec<u8> {
+    let mut buf = raw.to_vec();
+    for step in ancestors.iter().rev() {
+        let mut outer = Vec::with_capacity(buf.len() + 10);
+        write_varint(&mut outer, u64::from(step.tag) << 3 | 2);
+        write_varint(&mut outer, buf.len() as u64);
+        outer.extend_from_slice(&buf);
+        buf = outer;
+    }
+    buf
+}
+
+/// Encodes a length delimited field.
+#[cfg(feature = "protobuf-codec")]
+pub(crate) fn message_field(tag: u32, payload: &[u8]) -> Vec<u8> {
+    wrap(&[Step { tag, index: 0, count: 1 }], payload)
+}
+
+/// Gets the distinct field numbers in data and how many times they show up,
+/// up to the first malformed field.
+#[cfg(feature = "protobuf-codec")]
+pub(crate) fn tags(mut data: &[u8]) -> Vec<(u32, usize)> {
+    let mut tags: Vec<(u32, usize)> = Vec::new();
+    while let Ok((tag, ..)) = next_field(&mut data) {
+        match tags.iter_mut().find(|(t, _)| *t == tag) {
+            Some((_, count)) => *count += 1,
+            None => tags.push((tag, 1)),
+        }
+    }
+    tags
+}
+
+/// How a field is declared in its message.
+#[cfg(any(feature = "protobuf-codec", feature = "quick-protobuf-codec"))]
+#[derive(Clone, Copy, Default)]
+pub(crate) struct FieldKind {
+    /// Whether it's a singular message field.
+    pub message: bool,
+    /// The index of the oneof it belongs to.
+    pub oneof: Option<i32>,
+}
+
+#[cfg(any(feature = "protobuf-codec", feature = "quick-protobuf-codec"))]
+enum Piece<'a> {
+    Raw(&'a [u8]),
+    Folded(u32),
+}
+
+/// Folds all the occurrences of every singular message field in data into one.
+///
+/// rust-protobuf 2 and pb-rs replace a message field that shows up again
+/// instead of merging into it, while the folded data decodes to the merged
+/// message. `kind` tells how a field number is declared by the message at the
+/// path of field numbers. Returns `None` if data is malformed.
+#[cfg(any(feature = "protobuf-codec", feature = "quick-protobuf-codec"))]
+pub(crate) fn fold(
+    data: &[u8],
+    path: &mut Vec<u32>,
+    kind: &mut dyn FnMut(&[u32], u32) -> FieldKind,
+) -> Option<Vec<u8>> {
+    let mut kinds: HashMap<u32, FieldKind> = HashMap::new();
+    let mut pieces = Vec::new();
+    // The position of a folded field in pieces and its concatenated payloads.
+    let mut folded: HashMap<u32, (usize, Vec<u8>)> = HashMap::new();
+    let mut rest = data;
+    while !rest.is_empty() {
+        let (tag, raw, payload) = next_field(&mut rest).ok()?;
+        let k = *kinds.entry(tag).or_insert_with(|| kind(path, tag));
+        if let Some(oneof) = k.oneof {
+            // Setting a field of oneof discards the others.
+            let discarded: Vec<u32> = folded
+                .keys()
+                .filter(|t| **t != tag && kinds[*t].oneof == Some(oneof))
+                .cloned()
+                .collect();
+            for t in discarded {
+                let (pos, _) = folded.remove(&t).unwrap();
+                pieces[pos] = None;
+            }
+        }
+        match payload {
+            Some(payload) if k.message => {
+                let pos = pieces.len();
+                let entry = folded.entry(tag).or_insert_with(|| (pos, Vec::new()));
+                if entry.0 == pos {
+                    pieces.push(Some(Piece::Folded(tag)));
+                }
+                entry.1.extend_from_slice(payload);
+            }
+            _ => pieces.push(Some(Piece::Raw(raw))),
+        }
+    }
+    let mut buf = Vec::with_capacity(data.len());
+    for piece in pieces.into_iter().flatten() {
+        match piece {
+            Piece::Raw(raw) => buf.extend_from_slice(raw),
+            Piece::Folded(tag) => {
+                path.push(tag);
+                let payload = fold(&folded[&tag].1, path, kind)?;
+                path.pop();
+                write_varint(&mut buf, u64::from(tag) << 3 | 2);
+                write_varint(&mut buf, payload.len() as u64);
+                buf.extend_from_slice(&payload);
+            }
+        }
+    }
+    Some(buf)
+}
+
+/// Counts how many times every field number shows up in data.
+fn count(mut data: &[u8]) -> HashMap<u32, usize> {
+    let mut counts = HashMap::new();
+    while let Ok((tag, ..)) = next_field(&mut data) {
+        *counts.entry(tag).or_insert(0) += 1;
+    }
+    counts
+}
+
+/// Finds the path to the first field that fails to decode in data.
+///
+/// `is_valid` checks whether a message can be decoded, and `descend` tells if
+/// the last field of a path is a message that should be searched into.
+pub(crate) fn locate(
+    data: &[u8],
+    is_valid: &dyn Fn(&[u8]) -> bool,
+    descend: &mut dyn FnMut(&[Step]) -> bool,
+) -> Vec<Step> {
+    let mut path = Vec::new();
+    let mut level = data;
+    'outer: loop {
+        let mut data = level;
+        let counts = count(level);
+        let mut indexes = HashMap::new();
+        while !data.is_empty() {
+            let res = next_field(&mut data);
+            let tag = match res {
+                Ok((tag, ..)) | Err(Some(tag)) => tag,
+                Err(None) => break 'outer,
+            };
+            let index = indexes.entry(tag).or_insert(0);
+            let step = Step {
+                tag,
+                index: *index,
+                count: counts.get(&tag).copied().unwrap_or(0).max(*index + 1),
+            };
+            *index += 1;
+            let (raw, payload) = match res {
+                Ok((_, raw, payload)) => (raw, payload),
+                Err(_) => {
+                    path.push(step);
+                    break 'outer;
+                }
+            };
+            if is_valid(&wrap(&path, raw)) {
+                continue;
+            }
+            path.push(step);
+            match payload {
+                Some(payload) if descend(&path) => {
+                    level = payload;
+                    continue 'outer;
+                }
+                _ => break 'outer,
+            }
+        }
+        break;
+    }
+    path
+}
+
+/// Formats the path like `Request.mutations[3].key`.
+pub(crate) fn format(root: &str, segments: &[Segment]) -> String {
+    let mut s = root.to_owned();
+    for seg in segments {
+        s.push('.');
+        s.push_str(&seg.name);
+        if seg.indexed {
+            s.push_str(&format!("[{}]", seg.index));
+        }
+    }
+    s
+}
@@ -3,7 +3,7 @@ extern crate prost;
 
 #[cfg(feature = "prost-codec")]
 mod prost_tests {
-    use jinkela::GenericEnum;
+    use jinkela::{ErrorKind, GenericEnum, GenericMessage};
 
     #[derive(::jinkela::Classicalize, Default, Debug)]
     struct A {
@@ -29,6 +29,203 @@ mod prost_tests {
         b: u64,
     }
 
+    #[derive(::jinkela::Classicalize, Clone, PartialEq, ::prost::Message)]
+    struct Record {
+        #[prost(uint64, tag = "1")]
+        id: u64,
+        #[prost(bytes, tag = "2")]
+        value: Vec<u8>,
+        #[prost(message, optional, tag = "3")]
+        meta: Option<Meta>,
+        #[prost(uint64, repeated, tag = "4")]
+        versions: Vec<u64>,
+    }
+
+    #[derive(::jinkela::Classicalize, Clone, PartialEq, ::prost::Message)]
+    struct Batch {
+        #[prost(message, repeated, tag = "1")]
+        records: Vec<Record>,
+    }
+
+    #[derive(::jinkela::Classicalize, Clone, PartialEq, ::prost::Message)]
+    pub struct Request {
+        #[prost(uint64, tag = "1")]
+        id: u64,
+        #[prost(oneof = "request::Cmd", tags = "2, 3, 4, 5, 6")]
+        cmd: Option<request::Cmd>,
+    }
+
+    pub mod request {
+        #[derive(::jinkela::Classicalize, Clone, PartialEq, ::prost::Oneof)]
+        pub enum Cmd {
+            #[prost(string, tag = "2")]
+            Name(String),
+            #[prost(message, tag = "3")]
+            Meta(super::Meta),
+            #[prost(uint64, tag = "4")]
+            Ttl(u64),
+            #[prost(bytes, tag = "5")]
+            RawData(Vec<u8>),
+            #[prost(enumeration = "super::Level", tag = "6")]
+            Level(i32),
+        }
+    }
+
+    #[derive(::jinkela::Classicalize, Clone, PartialEq, ::prost::Message)]
+    struct Labels {
+        #[prost(map = "string, bytes", tag = "1")]
+        values: ::std::collections::HashMap<String, Vec<u8>>,
+        #[prost(map = "uint64, message", tag = "2")]
+        metas: ::std::collections::HashMap<u64, Meta>,
+        #[prost(btree_map = "string, enumeration(Level)", tag = "3")]
+        levels: ::std::collections::BTreeMap<String, i32>,
+    }
+
+    #[derive(::jinkela::Classicalize, Clone, PartialEq, ::prost::Message)]
+    pub struct Meta {
+        #[prost(string, tag = "1")]
+        owner: String,
+        #[prost(uint64, tag = "2")]
+        ttl: u64,
+    }
+
+    #[derive(::jinkela::Classicalize, Clone, PartialEq, ::prost::Message)]
+    struct Legacy {
+        #[prost(uint64, optional, tag = "1")]
+        id: Option<u64>,
+        #[prost(string, optional, tag = "2")]
+        name: Option<String>,
+        #[prost(bytes, optional, tag = "3")]
+        data: Option<Vec<u8>>,
+        #[prost(enumeration = "Level", optional, tag = "4")]
+        level: Option<i32>,
+        #[prost(int32, optional, tag = "5", default = "-5")]
+        offset: Option<i32>,
+        #[prost(double, optional, tag = "6", default = "inf")]
+        limit: Option<f64>,
+        #[prost(bool, optional, tag = "7", default = "true")]
+        enabled: Option<bool>,
+        #[prost(string, optional, tag = "8", default = "anonymous")]
+        owner: Option<String>,
+        #[prost(bytes, optional, tag = "9", default = "b\"\\x00x\"")]
+        magic: Option<Vec<u8>>,
+        #[prost(enumeration = "Level", optional, tag = "10", default = "HighPriority")]
+        priority: Option<i32>,
+        #[prost(enumeration = "Level", repeated, tag = "11")]
+        history: Vec<i32>,
+        #[prost(string, optional, tag = "12", default = " a ")]
+        padding: Option<String>,
+    }
+
+    #[derive(::jinkela::Classicalize, Clone, PartialEq, ::prost::Message)]
+    pub struct Node {
+        #[prost(uint64, tag = "1")]
+        value: u64,
+        #[prost(message, optional, boxed, tag = "2")]
+        next: Option<Box<Node>>,
+        #[prost(oneof = "node::Child", tags = "3")]
+        child: Option<node::Child>,
+    }
+
+    pub mod node {
+        #[derive(::jinkela::Classicalize, Clone, PartialEq, ::prost::Oneof)]
+        pub enum Child {
+            #[prost(message, tag = "3")]
+            Left(Box<super::Node>),
+        }
+    }
+
+    #[derive(::jinkela::Classicalize, Clone, PartialEq, ::prost::Message)]
+    #[jinkela(unknown_enum = "default")]
+    struct Lenient {
+        #[prost(enumeration = "Level", tag = "1")]
+        level: i32,
+        #[prost(enumeration = "Level", repeated, tag = "2")]
+        history: Vec<i32>,
+    }
+
+    #[derive(::jinkela::Classicalize, Default)]
+    struct Shared {
+        #[prost(bytes = "bytes")]
+        data: bytes::Bytes,
+        #[prost(bytes = "vec")]
+        raw: Vec<u8>,
+        #[prost(bytes = "bytes", optional, default = "b\"ab\"")]
+        tail: Option<bytes::Bytes>,
+        #[prost(bytes = "bytes", repeated)]
+        chunks: Vec<bytes::Bytes>,
+        #[prost(oneof = "shared::Payload", tags = "5")]
+        payload: Option<shared::Payload>,
+    }
+
+    pub mod shared {
+        #[derive(::jinkela::Classicalize, Clone, Debug, PartialEq)]
+        pub enum Payload {
+            #[prost(bytes = "bytes", tag = "5")]
+            Chunk(bytes::Bytes),
+        }
+    }
+
+    #[derive(Clone, PartialEq, ::prost::Message)]
+    struct Plain {
+        #[prost(uint64, tag = "1")]
+        id: u64,
+    }
+
+    jinkela::prost_message!(Plain);
+
+    mod reexport {
+        pub use jinkela as renamed;
+    }
+
+    #[derive(reexport::renamed::Classicalize, Default, Debug, PartialEq)]
+    #[jinkela(crate = "self::reexport::renamed")]
+    struct Renamed {
+        #[prost(message, optional)]
+        b: Option<B>,
+        #[prost(enumeration = "Color")]
+        color: i32,
+    }
+
+    #[derive(reexport::renamed::Classicalize, Clone, Copy, Debug, Default, PartialEq)]
+    #[jinkela(crate = "self::reexport::renamed")]
+    #[repr(i32)]
+    enum Color {
+        #[default]
+        Red = 0,
+        Blue = 1,
+    }
+
+    #[derive(::jinkela::Classicalize, Default, Debug, PartialEq)]
+    struct Wrapper<T, U: Clone>
+    where
+        T: Default,
+    {
+        #[prost(message, optional)]
+        inner: Option<T>,
+        #[prost(message, optional)]
+        nested: Option<Inner<T>>,
+        #[prost(message, repeated)]
+        items: Vec<U>,
+        #[prost(uint64)]
+        version: u64,
+        #[prost(oneof = "request::Cmd", tags = "2, 3, 4, 5, 6")]
+        cmd: Option<request::Cmd>,
+    }
+
+    #[derive(::jinkela::Classicalize, Default, Debug, PartialEq)]
+    struct Inner<T: Default> {
+        #[prost(message, optional)]
+        value: Option<T>,
+    }
+
+    #[derive(::jinkela::Classicalize, Default, Debug, PartialEq)]
+    struct Pair(
+        #[prost(string)] String,
+        #[prost(message, optional)] Option<B>,
+        #[prost(oneof = "request::Cmd", tags = "2, 3, 4, 5, 6")] Option<request::Cmd>,
+    );
+
     #[derive(::jinkela::Classicalize, Debug, PartialEq)]
     #[repr(i32)]
     enum E {
@@ -36,20 +233,27 @@ mod prost_tests {
         C = 1,
     }
 
+    #[derive(::jinkela::Classicalize, Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, ::prost::Enumeration)]
+    #[repr(i32)]
+    pub enum Level {
+        Low = 0,
+        HighPriority = 5,
+    }
+
     impl Default for E {
         fn default() -> E {
             E::T
         }
     }
 
-    impl E {
-        pub fn from_i32(i: i32) -> Option<E> {
-            match i {
-                0 => Some(E::T),
-                1 => Some(E::C),
-                _ => None
-            }
-        }
+    // Generated by jinkela-build for `enum Syntax { SYNTAX_PROTO2 = 0; SYNTAX_PROTO3 = 1; }`.
+    #[derive(::jinkela::Classicalize, Clone, Copy, Debug, PartialEq, Eq, ::prost::Enumeration)]
+    #[repr(i32)]
+    pub enum Syntax {
+        #[jinkela(name = "SYNTAX_PROTO2")]
+        Proto2 = 0,
+        #[jinkela(name = "SYNTAX_PROTO3")]
+        Proto3 = 1,
     }
 
     #[test]
@@ -89,8 +293,1321 @@ mod prost_tests {
         assert!(a.get_notify_only());
     }
 
+    #[test]
+    fn test_clear() {
+        let mut a = A::default();
+        a.mut_b1().b = 1;
+        a.mut_b3().push(B::default());
+        a.r#type = E::C as i32;
+        a.set_notify_only(true);
+        a.set_cf("cf".to_owned());
+        a.set_key(b"key".to_vec());
+        a.clear_b1();
+        a.clear_b3();
+        a.clear_type();
+        a.clear_notify_only();
+        a.clear_cf();
+        a.clear_key();
+        assert!(!a.has_b1());
+        assert!(a.get_b3().is_empty());
+        assert_eq!(a.get_type(), E::T);
+        assert!(!a.get_notify_only());
+        assert_eq!(a.get_cf(), "");
+        assert_eq!(a.get_key(), b"");
+
+        let mut l = Labels::default();
+        l.mut_values().insert("k".to_owned(), b"v".to_vec());
+        l.clear_values();
+        assert!(l.get_values().is_empty());
+
+        let mut r = Record::default();
+        r.set_id(1);
+        r.mut_meta().set_owner("tikv".to_owned());
+        r.set_versions(vec![1, 2]);
+        GenericMessage::clear(&mut r);
+        assert_eq!(r, Record::default());
+    }
+
     #[test]
     fn test_enum() {
         assert_eq!(E::values(), &[E::T, E::C]);
+        assert_eq!(E::C.value(), 1);
+        assert_eq!(E::from_i32(0), Some(E::T));
+        assert_eq!(E::from_i32(2), None);
+        assert_eq!(E::C.name(), "C");
+        assert_eq!(E::from_str_name("C"), Some(E::C));
+        assert_eq!(E::from_str_name("E_T"), Some(E::T));
+        assert_eq!(E::from_str_name("D"), None);
+
+        assert_eq!(Level::HighPriority.name(), "HIGH_PRIORITY");
+        assert_eq!(Level::HighPriority.value(), 5);
+        assert_eq!(Level::from_i32(5), Some(Level::HighPriority));
+        assert_eq!(Level::from_str_name("LEVEL_HIGH_PRIORITY"), Some(Level::HighPriority));
+
+        for s in Syntax::values() {
+            assert_eq!(Syntax::from_str_name(s.name()), Some(*s));
+        }
+        assert_eq!(Syntax::Proto3.name(), "SYNTAX_PROTO3");
+        assert_eq!(Syntax::from_str_name("PROTO3"), None);
+    }
+
+    #[test]
+    fn test_length_delimited() {
+        let mut r1 = Record::default();
+        r1.set_id(1);
+        r1.set_value(b"jinkela".to_vec());
+        let mut r2 = Record::default();
+        r2.set_id(2);
+
+        let mut buf = vec![];
+        r1.encode_length_delimited_into(&mut buf).unwrap();
+        assert_eq!(buf.len(), r1.compute_length_delimited_size());
+        assert_eq!(buf.len(), r1.compute_size() + 1);
+        let first = buf.len();
+        r2.encode_length_delimited_into(&mut buf).unwrap();
+
+        assert_eq!(Record::decode_length_delimited_from(&buf).unwrap(), r1);
+        assert_eq!(Record::decode_length_delimited_from(&buf[first..]).unwrap(), r2);
+        assert!(Record::decode_length_delimited_from(&buf[..first - 1]).is_err());
+    }
+
+    #[test]
+    fn test_merge() {
+        let mut base = Record::default();
+        base.set_id(1);
+        base.set_value(b"v1".to_vec());
+        base.mut_meta().set_owner("tikv".to_owned());
+        base.mut_meta().set_ttl(10);
+        base.set_versions(vec![1, 2]);
+
+        let mut update = Record::default();
+        update.set_id(2);
+        update.mut_meta().set_ttl(20);
+        update.set_versions(vec![3]);
+        let mut buf = vec![];
+        update.encode_into(&mut buf).unwrap();
+
+        base.merge_from(&buf).unwrap();
+        assert_eq!(base.get_id(), 2);
+        assert_eq!(base.get_value(), b"v1");
+        assert_eq!(base.get_meta().get_owner(), "tikv");
+        assert_eq!(base.get_meta().get_ttl(), 20);
+        assert_eq!(base.get_versions(), &[1, 2, 3]);
+
+        assert!(base.merge_from(&buf[..buf.len() - 1]).is_err());
+    }
+
+    #[test]
+    fn test_buf_and_io() {
+        let mut r = Record::default();
+        r.set_id(4);
+        r.set_value(b"arena".to_vec());
+        r.mut_meta().set_owner("pd".to_owned());
+        let size = r.compute_size();
+
+        let mut buf = bytes::BytesMut::with_capacity(size);
+        r.encode_into_buf(&mut buf).unwrap();
+        assert_eq!(buf.len(), size);
+        assert_eq!(Record::decode_from_buf(std::io::Cursor::new(&buf[..])).unwrap(), r);
+
+        let mut arena = [0u8; 64];
+        r.encode_into_buf(&mut std::io::Cursor::new(&mut arena[..])).unwrap();
+        assert_eq!(Record::decode_from(&arena[..size]).unwrap(), r);
+        let mut small = std::io::Cursor::new(&mut arena[..size - 1]);
+        assert!(r.encode_into_buf(&mut small).is_err());
+        assert_eq!(small.position(), 0);
+
+        let mut sink = vec![];
+        r.encode_into_writer(&mut sink).unwrap();
+        assert_eq!(sink, &buf[..]);
+        assert_eq!(Record::decode_from_reader(&mut &sink[..]).unwrap(), r);
+    }
+
+    #[test]
+    fn test_error_kind() {
+        let mut r = Record::default();
+        r.set_value(b"value".to_vec());
+        let mut buf = vec![];
+        r.encode_into(&mut buf).unwrap();
+
+        let e = Record::decode_from(&buf[..buf.len() - 1]).unwrap_err();
+        assert_eq!(e.kind(), ErrorKind::TruncatedInput);
+        let e: std::io::Error = e.into();
+        assert_eq!(e.kind(), std::io::ErrorKind::UnexpectedEof);
+
+        // Field 1 with wire type 7.
+        let e = Record::decode_from(&[0x0f]).unwrap_err();
+        assert_eq!(e.kind(), ErrorKind::InvalidWireType);
+
+        let e = Record::decode_from(&[0x08, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff]).unwrap_err();
+        assert_eq!(e.kind(), ErrorKind::InvalidVarint);
+
+        let mut arena = [0u8; 2];
+        let e = r.encode_into_buf(&mut std::io::Cursor::new(&mut arena[..])).unwrap_err();
+        assert_eq!(e.kind(), ErrorKind::BufferTooSmall);
+
+        fn assert_error<E: std::error::Error + Send + Sync + 'static>(_: &E) {}
+        assert_error(&e);
+    }
+
+    #[test]
+    fn test_error_path() {
+        let mut batch = Batch::default();
+        for id in 0..3 {
+            let mut r = Record::default();
+            r.set_id(id);
+            r.mut_meta().set_owner("tikv".to_owned());
+            batch.mut_records().push(r);
+        }
+        let mut buf = vec![];
+        batch.encode_into(&mut buf).unwrap();
+
+        let e = Batch::decode_from(&buf[..buf.len() - 1]).unwrap_err();
+        assert_eq!(e.kind(), ErrorKind::TruncatedInput);
+        assert_eq!(e.path(), Some("Batch.records[2]"));
+
+        let pos = buf.iter().rposition(|b| *b == b't').unwrap();
+        buf[pos] = 0xff;
+        let e = Batch::decode_from(&buf).unwrap_err();
+        assert_eq!(e.kind(), ErrorKind::InvalidUtf8);
+        assert_eq!(e.path(), Some("Batch.records[2].meta.owner"));
+        assert!(e.to_string().contains("Batch.records[2].meta.owner"), "{}", e);
+
+        // Field names are read from how PROST! formats its errors, which must
+        // keep the failing field first.
+        let e = <Batch as prost::Message>::decode(&buf[..]).unwrap_err();
+        assert_eq!(
+            e.to_string(),
+            "failed to decode Protobuf message: Meta.owner: Record.meta: Batch.records: invalid string value: data is not UTF-8 encoded"
+        );
+
+        let mut prefixed = vec![];
+        batch.encode_length_delimited_into(&mut prefixed).unwrap();
+        let pos = prefixed.iter().position(|b| *b == b't').unwrap();
+        prefixed[pos] = 0xff;
+        let e = Batch::decode_length_delimited_from(&prefixed).unwrap_err();
+        assert_eq!(e.path(), Some("Batch.records[0].meta.owner"));
+
+        let e = Batch::decode_from_buf(std::io::Cursor::new(&buf[..])).unwrap_err();
+        assert_eq!(e.path(), Some("Batch.records[2].meta.owner"));
+    }
+
+    #[test]
+    fn test_encode_into_writer() {
+        let mut r = Record::default();
+        r.set_id(6);
+        r.set_value(vec![7; 10000]);
+        let mut buf = vec![];
+        r.encode_into(&mut buf).unwrap();
+
+        // Larger than a chunk.
+        let mut sink = vec![];
+        r.encode_into_writer(&mut sink).unwrap();
+        assert_eq!(sink, buf);
+
+        let mut arena = [0u8; 5000];
+        let e = r.encode_into_writer(&mut &mut arena[..]).unwrap_err();
+        assert_eq!(e.kind(), ErrorKind::BufferTooSmall);
+        assert_eq!(&arena[..], &buf[..5000]);
+    }
+
+    #[test]
+    fn test_oneof() {
+        let mut r = Request::default();
+        assert!(!r.has_name());
+        assert_eq!(r.get_name(), "");
+        assert_eq!(r.get_meta(), Meta::default_instance());
+        assert_eq!(r.get_ttl(), 0);
+        assert_eq!(r.get_raw_data(), b"");
+        assert_eq!(r.get_level(), Level::Low);
+
+        r.set_name("jinkela".to_owned());
+        assert!(r.has_name());
+        assert_eq!(r.get_name(), "jinkela");
+        r.mut_name().push('!');
+        assert_eq!(r.take_name(), "jinkela!");
+        assert!(!r.has_name());
+        assert_eq!(r.take_name(), "");
+
+        r.mut_meta().set_ttl(3);
+        assert!(r.has_meta());
+        assert_eq!(r.get_meta().get_ttl(), 3);
+        r.set_ttl(5);
+        assert!(!r.has_meta());
+        assert_eq!(r.get_ttl(), 5);
+        r.set_raw_data(b"raw".to_vec());
+        assert_eq!(r.get_raw_data(), b"raw");
+        assert_eq!(r.get_ttl(), 0);
+        r.clear_raw_data();
+        assert!(r.cmd.is_none());
+
+        r.set_level(Level::HighPriority);
+        assert!(r.has_level());
+        assert_eq!(r.get_level(), Level::HighPriority);
+        let mut buf = vec![];
+        r.encode_into(&mut buf).unwrap();
+        assert_eq!(Request::decode_from(&buf).unwrap().get_level(), Level::HighPriority);
+    }
+
+    #[test]
+    fn test_map() {
+        let mut l = Labels::default();
+        assert!(l.get_values().is_empty());
+        l.mut_values().insert("k".to_owned(), b"v".to_vec());
+        assert_eq!(l.get_values()["k"], b"v");
+        let mut metas = std::collections::HashMap::new();
+        metas.insert(1, Meta::default());
+        l.set_metas(metas.clone());
+        assert_eq!(l.get_metas(), &metas);
+        l.mut_levels().insert("l".to_owned(), Level::HighPriority as i32);
+
+        let mut buf = vec![];
+        l.encode_into(&mut buf).unwrap();
+        let mut decoded = Labels::decode_from(&buf).unwrap();
+        assert_eq!(decoded, l);
+        assert_eq!(decoded.take_metas(), metas);
+        assert!(decoded.get_metas().is_empty());
+        assert_eq!(decoded.get_levels("l"), Some(Level::HighPriority));
+        let levels = decoded.get_levels_map();
+        assert_eq!(levels.len(), 1);
+        assert_eq!(levels["l"], Level::HighPriority);
+        assert_eq!(decoded.take_levels().len(), 1);
+        assert!(decoded.get_levels_map().is_empty());
+    }
+
+    #[test]
+    fn test_proto2_optional() {
+        let mut l = Legacy::default();
+        assert!(!l.has_id());
+        assert_eq!(l.get_id(), 0);
+        assert_eq!(l.get_name(), "");
+        assert_eq!(l.get_data(), b"");
+        assert!(!l.has_level());
+        assert_eq!(l.get_level(), Level::Low);
+
+        l.set_id(0);
+        assert!(l.has_id());
+        *l.mut_id() += 3;
+        assert_eq!(l.get_id(), 3);
+        assert_eq!(l.take_id(), 3);
+        assert!(!l.has_id());
+        l.set_id(4);
+        l.clear_id();
+        assert!(!l.has_id());
+
+        l.mut_name().push_str("name");
+        assert!(l.has_name());
+        assert_eq!(l.get_name(), "name");
+        assert_eq!(l.take_name(), "name");
+        assert!(!l.has_name());
+
+        l.set_data(b"data".to_vec());
+        assert_eq!(l.get_data(), b"data");
+        l.clear_data();
+        assert!(!l.has_data());
+        assert_eq!(l.take_data(), Vec::<u8>::new());
+
+        l.level = Some(Level::HighPriority as i32);
+        assert!(l.has_level());
+        assert_eq!(l.get_level(), Level::HighPriority);
+        l.clear_level();
+        assert_eq!(l.get_level(), Level::Low);
+
+        l.set_id(7);
+        let mut buf = vec![];
+        l.encode_into(&mut buf).unwrap();
+        let decoded = Legacy::decode_from(&buf).unwrap();
+        assert!(decoded.has_id());
+        assert!(!decoded.has_name());
+    }
+
+    #[test]
+    fn test_proto2_default() {
+        let mut l = Legacy::default();
+        assert!(!l.has_offset());
+        assert_eq!(l.get_offset(), -5);
+        assert_eq!(l.get_limit(), f64::INFINITY);
+        assert!(l.get_enabled());
+        assert_eq!(l.get_owner(), "anonymous");
+        assert_eq!(l.get_padding(), " a ");
+        assert_eq!(l.get_magic(), b"\0x");
+        assert_eq!(l.get_priority(), Level::HighPriority);
+
+        l.set_offset(1);
+        l.set_enabled(false);
+        l.mut_owner().push_str("root");
+        l.priority = Some(Level::Low as i32);
+        assert_eq!(l.get_offset(), 1);
+        assert!(!l.get_enabled());
+        assert_eq!(l.get_owner(), "root");
+        assert_eq!(l.get_priority(), Level::Low);
+        l.clear_owner();
+        assert_eq!(l.get_owner(), "anonymous");
+    }
+
+    #[test]
+    fn test_boxed_message() {
+        let mut head = Node::default();
+        assert!(!head.has_next());
+        assert_eq!(head.get_next(), Node::default_instance());
+        head.mut_next().value = 1;
+        head.mut_next().mut_next().value = 2;
+        assert_eq!(head.get_next().get_next().value, 2);
+        assert!(!head.get_next().get_next().has_next());
+
+        let mut buf = vec![];
+        head.encode_into(&mut buf).unwrap();
+        let mut decoded = Node::decode_from(&buf).unwrap();
+        assert_eq!(decoded, head);
+        let next = decoded.take_next();
+        assert_eq!(next.value, 1);
+        assert!(!decoded.has_next());
+        decoded.set_next(next);
+        assert_eq!(decoded, head);
+
+        assert!(!head.has_left());
+        assert_eq!(head.get_left(), Node::default_instance());
+        head.mut_left().value = 3;
+        assert_eq!(head.get_left().value, 3);
+        let left = head.take_left();
+        assert!(!head.has_left());
+        head.set_left(left);
+        assert_eq!(head.get_left().value, 3);
+    }
+
+    #[test]
+    fn test_enum_accessors() {
+        let mut l = Legacy::default();
+        l.set_level(Level::HighPriority);
+        assert_eq!(l.get_level(), Level::HighPriority);
+
+        assert!(l.get_history().is_empty());
+        l.set_history(vec![Level::Low, Level::HighPriority]);
+        assert_eq!(l.history, vec![0, 5]);
+        l.mut_history().push(Level::Low as i32);
+        assert_eq!(l.get_history(), vec![Level::Low, Level::HighPriority, Level::Low]);
+        assert_eq!(l.take_history().len(), 3);
+        assert!(l.history.is_empty());
+        l.set_history(vec![Level::Low]);
+        l.clear_history();
+        assert!(l.get_history().is_empty());
+    }
+
+    #[test]
+    fn test_unknown_enum() {
+        let mut l = Legacy::default();
+        assert_eq!(l.try_get_level(), Ok(Level::Low));
+        l.level = Some(3);
+        assert_eq!(l.try_get_level(), Err(3));
+        l.history = vec![5, 3];
+        assert_eq!(l.try_get_history(), Err(3));
+        let r = Request { cmd: Some(request::Cmd::Level(3)), ..Default::default() };
+        assert_eq!(r.try_get_level(), Err(3));
+
+        let l = Lenient { level: 3, history: vec![5, 3] };
+        assert_eq!(l.try_get_level(), Err(3));
+        assert_eq!(l.get_level(), Level::Low);
+        assert_eq!(l.get_history(), vec![Level::HighPriority, Level::Low]);
+    }
+
+    #[test]
+    #[should_panic(expected = "Unexpected enum value for level: 3")]
+    fn test_unknown_enum_panic() {
+        let l = Legacy { level: Some(3), ..Default::default() };
+        l.get_level();
+    }
+
+    #[test]
+    fn test_generic_and_tuple() {
+        let mut w: Wrapper<B, Meta> = Wrapper::default();
+        assert!(!w.has_inner());
+        assert_eq!(w.get_inner(), B::default_instance());
+        w.mut_inner().b = 3;
+        w.mut_items().push(Meta::default());
+        w.set_version(2);
+        w.set_ttl(10);
+        assert_eq!(w.get_inner().b, 3);
+        assert_eq!(w.get_items().len(), 1);
+        assert_eq!(w.get_version(), 2);
+        assert_eq!(w.get_ttl(), 10);
+        assert_eq!(*w.get_nested(), Inner::default());
+        w.mut_nested().mut_value().b = 4;
+        assert_eq!(w.get_nested().get_value().b, 4);
+        assert_eq!(w.take_nested().get_value().b, 4);
+        assert!(!w.has_nested());
+        assert_eq!(*Wrapper::<B, Meta>::default_instance(), Wrapper::default());
+        assert!(std::ptr::eq(
+            Wrapper::<B, Meta>::default_instance(),
+            Wrapper::<B, Meta>::default_instance()
+        ));
+        let addr = Wrapper::<B, Meta>::default_instance() as *const _ as usize;
+        let other = std::thread::spawn(|| Wrapper::<B, Meta>::default_instance() as *const _ as usize);
+        assert_eq!(other.join().unwrap(), addr);
+
+        let mut p = Pair::default();
+        p.set_0("key".to_owned());
+        p.mut_1().b = 1;
+        p.set_name("name".to_owned());
+        assert_eq!(p.get_0(), "key");
+        assert_eq!(p.get_1().b, 1);
+        assert_eq!(p.get_name(), "name");
+        assert_eq!(p.take_0(), "key");
+        assert!(p.has_1());
+        p.clear_1();
+        assert!(!p.has_1());
+    }
+
+    #[test]
+    fn test_crate_path() {
+        let mut r = Renamed::default();
+        assert_eq!(r.get_b(), B::default_instance());
+        r.set_color(Color::Blue);
+        assert_eq!(r.get_color(), Color::Blue);
+        assert_eq!(Color::from_str_name("BLUE"), Some(Color::Blue));
+        assert_eq!(Renamed::default_instance(), &Renamed::default());
+    }
+
+    #[test]
+    fn test_bytes_fields() {
+        let mut s = Shared::default();
+        let data = bytes::Bytes::from(vec![1, 2, 3]);
+        s.set_data(data.clone());
+        assert_eq!(s.get_data(), &[1, 2, 3]);
+        assert_eq!(s.take_data(), data);
+        assert!(s.get_data().is_empty());
+        s.set_raw(vec![4]);
+        assert_eq!(s.get_raw(), &[4]);
+        s.clear_raw();
+        assert!(s.get_raw().is_empty());
+
+        assert!(!s.has_tail());
+        assert_eq!(s.get_tail(), b"ab");
+        *s.mut_tail() = bytes::Bytes::from(&b"cd"[..]);
+        assert_eq!(s.get_tail(), b"cd");
+        assert_eq!(s.take_tail(), bytes::Bytes::from(&b"cd"[..]));
+        assert_eq!(s.get_tail(), b"ab");
+
+        s.mut_chunks().push(data.clone());
+        assert_eq!(s.get_chunks(), std::slice::from_ref(&data));
+
+        assert_eq!(s.get_chunk(), b"");
+        s.set_chunk(data.clone());
+        assert!(s.has_chunk());
+        assert_eq!(s.get_chunk(), &[1, 2, 3]);
+        assert_eq!(s.take_chunk(), data);
+        assert!(!s.has_chunk());
+    }
+
+    #[test]
+    fn test_prost_message() {
+        let p = Plain { id: 3 };
+        let mut buf = vec![];
+        p.encode_into(&mut buf).unwrap();
+        assert_eq!(Plain::decode_from(&buf).unwrap(), p);
+
+        let d = prost_types::Duration { seconds: 3, nanos: 0 };
+        buf.clear();
+        d.encode_into(&mut buf).unwrap();
+        assert_eq!(buf.len(), d.compute_size());
+        assert_eq!(prost_types::Duration::decode_from(&buf).unwrap(), d);
+    }
+
+    mod compat {
+        // Code written against rust-protobuf imports the compat trait only.
+        use super::Record;
+        use jinkela::compat::Message;
+
+        #[test]
+        fn test_compat() {
+            let mut r = Record::new();
+            r.set_id(5);
+            r.set_versions(vec![1]);
+            let buf = r.write_to_bytes().unwrap();
+            assert_eq!(buf.len(), r.compute_size() as usize);
+            assert_eq!(jinkela::compat::parse_from_bytes::<Record>(&buf).unwrap(), r);
+            let mut reader: &[u8] = &buf;
+            assert_eq!(jinkela::compat::parse_from_reader::<Record>(&mut reader).unwrap(), r);
+
+            let delimited = r.write_length_delimited_to_bytes().unwrap();
+            assert_eq!(delimited.len(), buf.len() + 1);
+            assert_eq!(jinkela::compat::parse_length_delimited_from_bytes::<Record>(&delimited).unwrap(), r);
+
+            let mut merged = Record::new();
+            merged.set_versions(vec![0]);
+            merged.merge_from_bytes(&buf).unwrap();
+            assert_eq!(merged.get_id(), 5);
+            assert_eq!(merged.get_versions(), &[0, 1]);
+            assert!(merged.merge_from_bytes(&buf[..buf.len() - 1]).is_err());
+        }
+    }
+
+    #[test]
+    fn test_shim_fields() {
+        use jinkela::{RepeatedField, SingularPtrField};
+
+        let mut r = Record::default();
+        r.set_id(1);
+        let mut b = Batch::default();
+        b.set_records(RepeatedField::from_vec(vec![r.clone()]));
+        assert_eq!(b.get_records(), &[r.clone()]);
+        b.set_records(vec![r.clone(), r.clone()]);
+        assert_eq!(b.get_records().len(), 2);
+        let records: RepeatedField<Record> = b.take_records().into_iter().collect();
+        assert_eq!(records.len(), 2);
+        assert_eq!(records.into_vec(), vec![r.clone(), r]);
+
+        let mut meta = Meta::default();
+        meta.set_ttl(3);
+        let mut r = Record::default();
+        r.set_meta(meta.clone());
+        assert_eq!(r.get_meta(), &meta);
+        r.set_meta(SingularPtrField::none());
+        assert!(!r.has_meta());
+        r.set_meta(SingularPtrField::some(meta.clone()));
+        assert_eq!(r.get_meta(), &meta);
+        r.set_meta(None);
+        assert!(!r.has_meta());
+
+        let mut n = Node::default();
+        n.set_next(SingularPtrField::some(Node::default()));
+        assert!(n.has_next());
+
+        let mut f = SingularPtrField::<Meta>::default();
+        assert!(f.is_none());
+        f.set_default().set_ttl(4);
+        assert_eq!(f.as_ref().map(|m| m.get_ttl()), Some(4));
+        assert_eq!(Option::from(f).map(|m: Meta| m.get_ttl()), Some(4));
+
+        let mut f = RepeatedField::<Meta>::new();
+        f.push_default().set_ttl(5);
+        f.push(Meta::default());
+        assert_eq!(f[0].get_ttl(), 5);
+        assert_eq!(Vec::from(f.clone()).len(), 2);
+        assert_eq!((&f).into_iter().count(), 2);
+    }
+}
+
+#[cfg(feature = "protobuf-codec")]
+mod protobuf_tests {
+    use jinkela::{ErrorKind, GenericMessage};
+    use protobuf::well_known_types::{self, Api, Duration, Method};
+    use protobuf::RepeatedField;
+
+    fn duration(seconds: i64, nanos: i32) -> Duration {
+        let mut d = Duration::new();
+        d.set_seconds(seconds);
+        d.set_nanos(nanos);
+        d
+    }
+
+    #[test]
+    fn test_length_delimited() {
+        let d1 = duration(3, 5);
+        let d2 = duration(300, 0);
+        let mut buf = vec![];
+        d1.encode_into(&mut buf).unwrap();
+        assert_eq!(buf, [8, 3, 16, 5]);
+
+        buf.clear();
+        d1.encode_length_delimited_into(&mut buf).unwrap();
+        assert_eq!(buf, [4, 8, 3, 16, 5]);
+        assert_eq!(buf.len(), d1.compute_length_delimited_size());
+        d2.encode_length_delimited_into(&mut buf).unwrap();
+        assert_eq!(&buf[5..], [3, 8, 172, 2]);
+
+        assert_eq!(Duration::decode_length_delimited_from(&buf).unwrap(), d1);
+        assert_eq!(Duration::decode_length_delimited_from(&buf[5..]).unwrap(), d2);
+        let err = Duration::decode_length_delimited_from(&buf[..4]).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::TruncatedInput);
+    }
+
+    #[test]
+    fn test_merge() {
+        let mut base = Api::new();
+        base.set_name("base".to_owned());
+        base.set_version("v1".to_owned());
+        base.mut_methods().push(Method::new());
+        base.mut_source_context().set_file_name("a.proto".to_owned());
+
+        let mut update = Api::new();
+        update.set_name("update".to_owned());
+        update.mut_methods().push(Method::new());
+        update.mut_source_context().set_file_name("b.proto".to_owned());
+        let mut buf = vec![];
+        update.encode_into(&mut buf).unwrap();
+
+        base.merge_from(&buf).unwrap();
+        assert_eq!(base.get_name(), "update");
+        assert_eq!(base.get_version(), "v1");
+        assert_eq!(base.get_methods().len(), 2);
+        assert_eq!(base.get_source_context().get_file_name(), "b.proto");
+
+        let err = base.merge_from(&buf[..buf.len() - 1]).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::TruncatedInput);
+
+        base.clear();
+        assert_eq!(base, Api::new());
+    }
+
+    #[test]
+    fn test_merge_sub_messages() {
+        let mut base = well_known_types::Option::new();
+        base.set_name("deprecated".to_owned());
+        base.mut_value().set_type_url("bool".to_owned());
+
+        let mut update = well_known_types::Option::new();
+        update.mut_value().set_value(vec![1]);
+        let mut buf = vec![];
+        update.encode_into(&mut buf).unwrap();
+
+        base.merge_from(&buf).unwrap();
+        assert_eq!(base.get_name(), "deprecated");
+        assert_eq!(base.get_value().get_type_url(), "bool");
+        assert_eq!(base.get_value().get_value(), &[1]);
+
+        // Occurrences of the same field in data are merged too.
+        let mut twice = vec![];
+        base.encode_into(&mut twice).unwrap();
+        update.mut_value().set_value(vec![2]);
+        update.encode_into(&mut twice).unwrap();
+        let mut merged = well_known_types::Option::new();
+        merged.merge_from(&twice).unwrap();
+        assert_eq!(merged.get_value().get_type_url(), "bool");
+        assert_eq!(merged.get_value().get_value(), &[2]);
+
+        let mut value = well_known_types::Value::new();
+        value.mut_struct_value().mut_fields().insert("a".to_owned(), Default::default());
+        let mut other = well_known_types::Value::new();
+        other.mut_struct_value().mut_fields().insert("b".to_owned(), Default::default());
+        let mut buf = vec![];
+        other.encode_into(&mut buf).unwrap();
+        value.merge_from(&buf).unwrap();
+        assert_eq!(value.get_struct_value().get_fields().len(), 2);
+
+        // Another field of the oneof replaces the message.
+        let mut number = vec![];
+        let mut other = well_known_types::Value::new();
+        other.set_number_value(1.0);
+        other.encode_into(&mut number).unwrap();
+        value.merge_from(&number).unwrap();
+        assert!(!value.has_struct_value());
+        value.merge_from(&buf).unwrap();
+        assert_eq!(value.get_struct_value().get_fields().len(), 1);
+        number.extend_from_slice(&buf);
+        value.merge_from(&number).unwrap();
+        assert_eq!(value.get_struct_value().get_fields().len(), 1);
+    }
+
+    #[test]
+    fn test_enum() {
+        use jinkela::GenericEnum;
+        use protobuf::well_known_types::Field_Kind;
+
+        assert_eq!(Field_Kind::values().len(), 19);
+        assert_eq!(Field_Kind::TYPE_STRING.value(), 9);
+        assert_eq!(Field_Kind::from_i32(9), Some(Field_Kind::TYPE_STRING));
+        assert_eq!(Field_Kind::TYPE_STRING.name(), "TYPE_STRING");
+        assert_eq!(Field_Kind::from_str_name("TYPE_BOOL"), Some(Field_Kind::TYPE_BOOL));
+        assert_eq!(Field_Kind::from_str_name("BOOL"), None);
+    }
+
+    #[cfg(feature = "protobuf-with-bytes")]
+    #[test]
+    fn test_decode_from_bytes() {
+        use jinkela::ZeroCopyMessage;
+
+        let d = duration(3, 5);
+        let mut buf = vec![];
+        d.encode_into(&mut buf).unwrap();
+        let truncated = bytes::Bytes::from(buf[..buf.len() - 1].to_vec());
+        assert_eq!(Duration::decode_from_bytes(bytes::Bytes::from(buf)).unwrap(), d);
+        let err = Duration::decode_from_bytes(truncated).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::TruncatedInput);
+        assert_eq!(err.path(), Some("Duration.nanos"));
+    }
+
+    #[test]
+    fn test_buf_and_io() {
+        let d = duration(-1, 7);
+        let size = d.compute_size();
+
+        let mut buf = bytes::BytesMut::with_capacity(size);
+        d.encode_into_buf(&mut buf).unwrap();
+        assert_eq!(buf.len(), size);
+        assert_eq!(Duration::decode_from_buf(std::io::Cursor::new(&buf[..])).unwrap(), d);
+
+        let mut arena = [0u8; 16];
+        d.encode_into_buf(&mut std::io::Cursor::new(&mut arena[..])).unwrap();
+        assert_eq!(Duration::decode_from(&arena[..size]).unwrap(), d);
+        let mut small = std::io::Cursor::new(&mut arena[..size - 1]);
+        let err = d.encode_into_buf(&mut small).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::BufferTooSmall);
+        assert_eq!(small.position(), 0);
+
+        let mut sink = vec![];
+        d.encode_into_writer(&mut sink).unwrap();
+        assert_eq!(sink, &buf[..]);
+        assert_eq!(Duration::decode_from_reader(&mut &sink[..]).unwrap(), d);
+        let err = Duration::decode_from_reader(&mut &sink[..size - 1]).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::TruncatedInput);
+    }
+
+    #[test]
+    fn test_error_path() {
+        let mut api = Api::new();
+        api.set_name("jinkela".to_owned());
+        let methods = ["get", "put"].iter().map(|n| {
+            let mut m = Method::new();
+            m.set_name(n.to_string());
+            m
+        });
+        api.set_methods(RepeatedField::from_vec(methods.collect()));
+        let mut buf = vec![];
+        api.encode_into(&mut buf).unwrap();
+        assert_eq!(&buf[..9], b"\n\x07jinkela");
+
+        // The third method is complete, but its name claims 4 bytes while only 2 follow.
+        let mut corrupted = buf.clone();
+        corrupted.extend_from_slice(&[0x12, 4, 0x0a, 4, b's', b'c']);
+        let err = Api::decode_from(&corrupted).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::TruncatedInput);
+        assert_eq!(err.path(), Some("Api.methods[2].name"));
+
+        let err = Api::decode_from(&buf[..buf.len() - 1]).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::TruncatedInput);
+        assert_eq!(err.path(), Some("Api.methods[1]"));
+
+        let err = Duration::decode_from(&[8, 3, 16]).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::TruncatedInput);
+        assert_eq!(err.path(), Some("Duration.nanos"));
+
+        let mut delimited = vec![];
+        api.encode_length_delimited_into(&mut delimited).unwrap();
+        let err = Api::decode_length_delimited_from(&delimited[..delimited.len() - 1]).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::TruncatedInput);
+    }
+}
+
+#[cfg(feature = "protobuf-with-bytes")]
+mod protobuf_bytes_tests {
+    use jinkela::{GenericMessage, ZeroCopyMessage};
+    use jinkela::ErrorKind;
+    use protobuf::descriptor::{
+        DescriptorProto, FieldDescriptorProto, FieldDescriptorProto_Label, FieldDescriptorProto_Type,
+        FileDescriptorProto,
+    };
+    use protobuf::reflect::accessor::make_simple_field_accessor;
+    use protobuf::reflect::MessageDescriptor;
+    use protobuf::types::ProtobufTypeCarllercheBytes;
+    use protobuf::{CachedSize, CodedInputStream, CodedOutputStream, ProtobufResult, UnknownFields};
+    use std::any::Any;
+
+    // Written like the code generated by rust-protobuf with `carllerche_bytes_for_bytes`.
+    #[derive(Clone, Default, Debug, PartialEq)]
+    struct Blob {
+        data: bytes1::Bytes,
+        unknown_fields: UnknownFields,
+        cached_size: CachedSize,
+    }
+
+    fn file_descriptor_proto() -> &'static FileDescriptorProto {
+        static FILE: protobuf::rt::LazyV2<FileDescriptorProto> = protobuf::rt::LazyV2::INIT;
+        FILE.get(|| {
+            let mut field = FieldDescriptorProto::new();
+            field.set_name("data".to_owned());
+            field.set_number(1);
+            field.set_label(FieldDescriptorProto_Label::LABEL_OPTIONAL);
+            field.set_field_type(FieldDescriptorProto_Type::TYPE_BYTES);
+            let mut message = DescriptorProto::new();
+            message.set_name("Blob".to_owned());
+            message.mut_field().push(field);
+            let mut file = FileDescriptorProto::new();
+            file.set_name("blob.proto".to_owned());
+            file.set_syntax("proto3".to_owned());
+            file.mut_message_type().push(message);
+            file
+        })
+    }
+
+    impl protobuf::Message for Blob {
+        fn descriptor(&self) -> &'static MessageDescriptor {
+            Self::descriptor_static()
+        }
+
+        fn descriptor_static() -> &'static MessageDescriptor {
+            static DESCRIPTOR: protobuf::rt::LazyV2<MessageDescriptor> = protobuf::rt::LazyV2::INIT;
+            DESCRIPTOR.get(|| {
+                let fields = vec![make_simple_field_accessor::<_, ProtobufTypeCarllercheBytes>(
+                    "data",
+                    |m: &Blob| &m.data,
+                    |m: &mut Blob| &mut m.data,
+                )];
+                MessageDescriptor::new_pb_name::<Blob>("Blob", fields, file_descriptor_proto())
+            })
+        }
+
+        fn is_initialized(&self) -> bool {
+            true
+        }
+
+        fn merge_from(&mut self, is: &mut CodedInputStream) -> ProtobufResult<()> {
+            while !is.eof()? {
+                let (field_number, wire_type) = is.read_tag_unpack()?;
+                match field_number {
+                    1 => protobuf::rt::read_singular_proto3_carllerche_bytes_into(wire_type, is, &mut self.data)?,
+                    _ => protobuf::rt::read_unknown_or_skip_group(field_number, wire_type, is, &mut self.unknown_fields)?,
+                }
+            }
+            Ok(())
+        }
+
+        fn compute_size(&self) -> u32 {
+            let mut size = 0;
+            if !self.data.is_empty() {
+                size += protobuf::rt::bytes_size(1, &self.data);
+            }
+            size += protobuf::rt::unknown_fields_size(&self.unknown_fields);
+            self.cached_size.set(size);
+            size
+        }
+
+        fn write_to_with_cached_sizes(&self, os: &mut CodedOutputStream) -> ProtobufResult<()> {
+            if !self.data.is_empty() {
+                os.write_bytes(1, &self.data)?;
+            }
+            os.write_unknown_fields(&self.unknown_fields)
+        }
+
+        fn get_cached_size(&self) -> u32 {
+            self.cached_size.get()
+        }
+
+        fn get_unknown_fields(&self) -> &UnknownFields {
+            &self.unknown_fields
+        }
+
+        fn mut_unknown_fields(&mut self) -> &mut UnknownFields {
+            &mut self.unknown_fields
+        }
+
+        fn as_any(&self) -> &dyn Any {
+            self
+        }
+
+        fn new() -> Blob {
+            Blob::default()
+        }
+
+        fn default_instance() -> &'static Blob {
+            static INSTANCE: protobuf::rt::LazyV2<Blob> = protobuf::rt::LazyV2::INIT;
+            INSTANCE.get(Blob::new)
+        }
+    }
+
+    impl protobuf::Clear for Blob {
+        fn clear(&mut self) {
+            self.data.clear();
+            self.unknown_fields.clear();
+        }
+    }
+
+    jinkela::protobuf_message!(Blob);
+
+    #[test]
+    fn test_decode_from_bytes() {
+        let blob = Blob {
+            data: bytes1::Bytes::from_static(b"large value"),
+            ..Default::default()
+        };
+        let mut buf = vec![];
+        blob.encode_into(&mut buf).unwrap();
+        let data = bytes::Bytes::from(buf);
+
+        let decoded = Blob::decode_from_bytes(data.clone()).unwrap();
+        assert_eq!(decoded.data, blob.data);
+        let input = data.as_ptr() as usize..data.as_ptr() as usize + data.len();
+        assert!(input.contains(&(decoded.data.as_ptr() as usize)));
+
+        assert_eq!(jinkela::compat::parse_from_carllerche_bytes::<Blob>(&data).unwrap(), blob);
+
+        let copied = Blob::decode_from(&data).unwrap();
+        assert!(!input.contains(&(copied.data.as_ptr() as usize)));
+
+        let err = Blob::decode_from_bytes(bytes::Bytes::from(data[..data.len() - 1].to_vec())).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::TruncatedInput);
+        assert_eq!(err.path(), Some("Blob.data"));
+    }
+}
+
+#[cfg(all(feature = "protobuf-codec", feature = "prost-codec"))]
+mod mixed_tests {
+    use jinkela::GenericMessage;
+
+    #[derive(::jinkela::Classicalize, Clone, PartialEq, ::prost::Message)]
+    struct Duration {
+        #[prost(int64, tag = "1")]
+        seconds: i64,
+        #[prost(int32, tag = "2")]
+        nanos: i32,
+    }
+
+    fn convert<F: GenericMessage, T: GenericMessage>(from: &F) -> T
+    where
+        F::Error: std::fmt::Debug,
+        T::Error: std::fmt::Debug,
+    {
+        let mut buf = vec![];
+        from.encode_into(&mut buf).unwrap();
+        T::decode_from(&buf).unwrap()
+    }
+
+    #[test]
+    fn test_both_backends() {
+        let mut d = protobuf::well_known_types::Duration::new();
+        d.set_seconds(3);
+        d.set_nanos(5);
+        let p: Duration = convert(&d);
+        assert_eq!(p.get_seconds(), 3);
+        assert_eq!(p.get_nanos(), 5);
+        let back: protobuf::well_known_types::Duration = convert(&p);
+        assert_eq!(back, d);
+    }
+}
+
+#[cfg(feature = "quick-protobuf-codec")]
+mod quick_protobuf_tests {
+    use jinkela::{ErrorKind, GenericEnum, GenericMessage};
+    use quick_protobuf::sizeofs::{sizeof_len, sizeof_varint};
+    use quick_protobuf::{BytesReader, MessageRead, MessageWrite, Result, Writer, WriterBackend};
+
+    // Written like the code generated by pb-rs.
+    #[derive(Debug, Default, PartialEq, Clone)]
+    pub struct Point {
+        pub x: i64,
+        pub y: i64,
+    }
+
+    impl<'a> MessageRead<'a> for Point {
+        fn from_reader(r: &mut BytesReader, bytes: &'a [u8]) -> Result<Self> {
+            let mut msg = Self::default();
+            while !r.is_eof() {
+                match r.next_tag(bytes) {
+                    Ok(8) => msg.x = r.read_int64(bytes)?,
+                    Ok(16) => msg.y = r.read_int64(bytes)?,
+                    Ok(t) => {
+                        r.read_unknown(bytes, t)?;
+                    }
+                    Err(e) => return Err(e),
+                }
+            }
+            Ok(msg)
+        }
+    }
+
+    impl MessageWrite for Point {
+        fn get_size(&self) -> usize {
+            let x = if self.x == 0i64 { 0 } else { 1 + sizeof_varint(self.x as u64) };
+            let y = if self.y == 0i64 { 0 } else { 1 + sizeof_varint(self.y as u64) };
+            x + y
+        }
+
+        fn write_message<W: WriterBackend>(&self, w: &mut Writer<W>) -> Result<()> {
+            if self.x != 0i64 {
+                w.write_with_tag(8, |w| w.write_int64(self.x))?;
+            }
+            if self.y != 0i64 {
+                w.write_with_tag(16, |w| w.write_int64(self.y))?;
+            }
+            Ok(())
+        }
+    }
+
+    #[derive(Debug, Default, PartialEq, Clone)]
+    pub struct Line {
+        pub start: Option<Point>,
+    }
+
+    impl<'a> MessageRead<'a> for Line {
+        fn from_reader(r: &mut BytesReader, bytes: &'a [u8]) -> Result<Self> {
+            let mut msg = Self::default();
+            while !r.is_eof() {
+                match r.next_tag(bytes) {
+                    Ok(10) => msg.start = Some(r.read_message::<Point>(bytes)?),
+                    Ok(t) => {
+                        r.read_unknown(bytes, t)?;
+                    }
+                    Err(e) => return Err(e),
+                }
+            }
+            Ok(msg)
+        }
+    }
+
+    impl MessageWrite for Line {
+        fn get_size(&self) -> usize {
+            self.start.as_ref().map_or(0, |m| 1 + sizeof_len(m.get_size()))
+        }
+
+        fn write_message<W: WriterBackend>(&self, w: &mut Writer<W>) -> Result<()> {
+            if let Some(ref s) = self.start {
+                w.write_with_tag(10, |w| w.write_message(s))?;
+            }
+            Ok(())
+        }
+    }
+
+    #[allow(non_camel_case_types, clippy::upper_case_acronyms)]
+    #[derive(Debug, PartialEq, Eq, Clone, Copy)]
+    pub enum Shape {
+        UNKNOWN = 0,
+        SQUARE = 2,
+    }
+
+    jinkela::quick_protobuf_message!(Point {}, Line { ".Line" { 1 => ".Point" } });
+    jinkela::quick_protobuf_enum!(Shape { UNKNOWN, SQUARE });
+
+    #[test]
+    fn test_quick_protobuf() {
+        let p = Point { x: 3, y: 4 };
+        let mut buf = vec![];
+        p.encode_into(&mut buf).unwrap();
+        assert_eq!(buf.len(), p.compute_size());
+        assert_eq!(Point::decode_from(&buf).unwrap(), p);
+        assert_eq!(jinkela::quick_protobuf::decode_borrowed::<Point>(&buf).unwrap(), p);
+
+        let mut sink = vec![];
+        p.encode_into_writer(&mut sink).unwrap();
+        assert_eq!(sink, buf);
+        let mut arena = bytes::BytesMut::with_capacity(buf.len());
+        p.encode_into_buf(&mut arena).unwrap();
+        assert_eq!(&arena[..], &buf[..]);
+        let mut small = [0u8; 2];
+        let err = p.encode_into_buf(&mut std::io::Cursor::new(&mut small[..])).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::BufferTooSmall);
+
+        let mut merged = Point { x: 1, y: 0 };
+        merged.merge_from(&[16, 5]).unwrap();
+        assert_eq!(merged, Point { x: 1, y: 5 });
+        merged.clear();
+        assert_eq!(merged, Point::default());
+
+        let mut line = Line { start: Some(Point { x: 1, y: 0 }) };
+        let update = Line { start: Some(Point { x: 0, y: 5 }) };
+        let mut data = vec![];
+        update.encode_into(&mut data).unwrap();
+        line.merge_from(&data).unwrap();
+        assert_eq!(line, Line { start: Some(Point { x: 1, y: 5 }) });
+
+        // Occurrences of the same field in data are merged too.
+        let mut twice = vec![];
+        Line { start: Some(Point { x: 2, y: 0 }) }.encode_into(&mut twice).unwrap();
+        twice.extend_from_slice(&data);
+        let mut line = Line::default();
+        line.merge_from(&twice).unwrap();
+        assert_eq!(line, Line { start: Some(Point { x: 2, y: 5 }) });
+        let err = line.merge_from(&twice[..twice.len() - 1]).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::TruncatedInput);
+
+        let mut delimited = vec![];
+        p.encode_length_delimited_into(&mut delimited).unwrap();
+        assert_eq!(delimited.len(), p.compute_length_delimited_size());
+        assert_eq!(Point::decode_length_delimited_from(&delimited).unwrap(), p);
+
+        let err = Point::decode_from(&buf[..buf.len() - 1]).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::TruncatedInput);
+        assert_eq!(err.path(), Some("Point.#2"));
+
+        assert_eq!(Shape::values(), &[Shape::UNKNOWN, Shape::SQUARE]);
+        assert_eq!(Shape::SQUARE.value(), 2);
+        assert_eq!(Shape::from_i32(2), Some(Shape::SQUARE));
+        assert_eq!(Shape::from_i32(1), None);
+        assert_eq!(Shape::SQUARE.name(), "SQUARE");
+        assert_eq!(Shape::from_str_name("UNKNOWN"), Some(Shape::UNKNOWN));
+    }
+}
+
+#[cfg(feature = "protobuf3-codec")]
+mod protobuf3_tests {
+    use jinkela::{ErrorKind, GenericEnum, GenericMessage};
+    use protobuf3::well_known_types::duration::Duration;
+    use protobuf3::well_known_types::struct_::ListValue;
+    use protobuf3::well_known_types::type_::Syntax;
+
+    #[test]
+    fn test_protobuf3() {
+        let d = Duration { seconds: 3, nanos: 5, ..Default::default() };
+        let mut buf = vec![];
+        d.encode_into(&mut buf).unwrap();
+        assert_eq!(buf.len(), d.compute_size());
+        assert_eq!(Duration::decode_from(&buf).unwrap(), d);
+        assert_eq!(Duration::decode_from_reader(&mut buf.as_slice()).unwrap(), d);
+
+        let mut merged = Duration::default();
+        merged.merge_from(&[16, 7]).unwrap();
+        assert_eq!(merged.nanos, 7);
+        merged.clear();
+        assert_eq!(merged, Duration::default());
+
+        let mut delimited = vec![];
+        d.encode_length_delimited_into(&mut delimited).unwrap();
+        assert_eq!(delimited.len(), d.compute_length_delimited_size());
+        assert_eq!(Duration::decode_length_delimited_from(&delimited).unwrap(), d);
+
+        let err = Duration::decode_from(&buf[..buf.len() - 1]).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::TruncatedInput);
+        assert_eq!(err.path(), Some("Duration.nanos"));
+
+        assert_eq!(Syntax::values(), &[Syntax::SYNTAX_PROTO2, Syntax::SYNTAX_PROTO3]);
+        assert_eq!(Syntax::SYNTAX_PROTO3.value(), 1);
+        assert_eq!(Syntax::from_i32(1), Some(Syntax::SYNTAX_PROTO3));
+        assert_eq!(Syntax::SYNTAX_PROTO3.name(), "SYNTAX_PROTO3");
+        assert_eq!(Syntax::from_str_name("SYNTAX_PROTO2"), Some(Syntax::SYNTAX_PROTO2));
+        assert_eq!(Syntax::from_str_name("SYNTAX_PROTO4"), None);
+    }
+
+    /// Every reason rust-protobuf 3 reports is classified by its `Debug`.
+    #[test]
+    fn test_error_kind() {
+        use protobuf3::descriptor::uninterpreted_option::NamePart;
+        use protobuf3::well_known_types::api::Api;
+        use protobuf3::Message;
+
+        let cases: &[(&[u8], ErrorKind)] = &[
+            (&[8], ErrorKind::TruncatedInput),
+            // A method name running past the end of the method.
+            (&[0x12, 3, 0x0a, 5, b'a'], ErrorKind::TruncatedInput),
+            // Field 1 ending a group.
+            (&[0x0c], ErrorKind::InvalidWireType),
+            (&[0], ErrorKind::InvalidTag),
+            (&[8, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 1], ErrorKind::InvalidVarint),
+            (&[0x0a, 1, 0xff], ErrorKind::InvalidUtf8),
+        ];
+        for (data, kind) in cases {
+            assert_eq!(Api::decode_from(data).unwrap_err().kind(), *kind, "{:?}", data);
+        }
+
+        // Lists nested in values deeper than the limit of 100.
+        let mut list = ListValue::new();
+        for _ in 0..101 {
+            let mut value = protobuf3::well_known_types::struct_::Value::new();
+            value.set_list_value(list);
+            list = ListValue::new();
+            list.values.push(value);
+        }
+        let nested = list.write_to_bytes().unwrap();
+        let err = ListValue::decode_from(&nested).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::RecursionLimit);
+
+        struct Broken;
+        impl std::io::Read for Broken {
+            fn read(&mut self, _: &mut [u8]) -> std::io::Result<usize> {
+                Err(std::io::ErrorKind::ConnectionReset.into())
+            }
+        }
+        let err = Duration::decode_from_reader(&mut Broken).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::Io);
+
+        let err = NamePart::new().write_to_bytes().unwrap_err();
+        assert_eq!(jinkela::ProtobufError::from(err).kind(), ErrorKind::MissingRequiredField);
+
+        let d = Duration { seconds: 3, ..Default::default() };
+        let mut small = [0u8; 1];
+        let err = d.write_to(&mut protobuf3::CodedOutputStream::bytes(&mut small)).unwrap_err();
+        assert_eq!(jinkela::ProtobufError::from(err).kind(), ErrorKind::BufferTooSmall);
+    }
+}
+
+/// Only uses the traits and helpers, so it works without any backend.
+mod generic_tests {
+    use jinkela::{ErrorKind, GenericMessage, ProtobufError, RepeatedField};
+    use std::io::{Read, Write};
+
+    /// A message that encodes as its data.
+    #[derive(Default, Debug, PartialEq)]
+    struct Raw {
+        data: Vec<u8>,
+    }
+
+    impl GenericMessage for Raw {
+        type Error = ProtobufError;
+
+        fn compute_size(&self) -> usize {
+            self.data.len()
+        }
+
+        fn encode_into(&self, buf: &mut Vec<u8>) -> Result<(), ProtobufError> {
+            buf.extend_from_slice(&self.data);
+            Ok(())
+        }
+
+        fn encode_into_writer<W: Write>(&self, w: &mut W) -> Result<(), ProtobufError> {
+            w.write_all(&self.data)?;
+            Ok(())
+        }
+
+        fn decode_from(data: &[u8]) -> Result<Raw, ProtobufError> {
+            Ok(Raw { data: data.to_vec() })
+        }
+
+        fn decode_from_reader<R: Read>(r: &mut R) -> Result<Raw, ProtobufError> {
+            let mut data = Vec::new();
+            r.read_to_end(&mut data)?;
+            Ok(Raw { data })
+        }
+
+        fn merge_from(&mut self, data: &[u8]) -> Result<(), ProtobufError> {
+            self.data.extend_from_slice(data);
+            Ok(())
+        }
+
+        fn clear(&mut self) {
+            self.data.clear();
+        }
+
+        fn compute_length_delimited_size(&self) -> usize {
+            self.data.len() + 1
+        }
+
+        fn encode_length_delimited_into(&self, buf: &mut Vec<u8>) -> Result<(), ProtobufError> {
+            buf.push(self.data.len() as u8);
+            self.encode_into(buf)
+        }
+
+        fn decode_length_delimited_from(data: &[u8]) -> Result<Raw, ProtobufError> {
+            match data.split_first() {
+                Some((len, rest)) if rest.len() >= *len as usize => Raw::decode_from(&rest[..*len as usize]),
+                _ => Err(ProtobufError::new(ErrorKind::TruncatedInput, "")),
+            }
+        }
+    }
+
+    fn roundtrip<M: GenericMessage<Error = ProtobufError>>(msg: &M) -> Result<M, ProtobufError> {
+        let mut buf = Vec::with_capacity(msg.compute_length_delimited_size());
+        msg.encode_length_delimited_into(&mut buf)?;
+        jinkela::compat::parse_length_delimited_from_bytes(&buf)
+    }
+
+    #[test]
+    fn test_generic() {
+        let raw = Raw { data: b"jinkela".to_vec() };
+        assert_eq!(roundtrip(&raw).unwrap(), raw);
+        let err = Raw::decode_length_delimited_from(&[3, 0]).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::TruncatedInput);
+
+        let field: RepeatedField<Raw> = vec![Raw::default()].into();
+        assert_eq!(field.len(), 1);
+    }
+
+    // Messages implemented without bytes still get the methods when a backend enables it.
+    #[cfg(feature = "bytes")]
+    #[test]
+    fn test_default_buf() {
+        let raw = Raw { data: b"jinkela".to_vec() };
+        let mut arena = [0u8; 7];
+        raw.encode_into_buf(&mut std::io::Cursor::new(&mut arena[..])).unwrap();
+        assert_eq!(&arena, b"jinkela");
+        let err = raw.encode_into_buf(&mut std::io::Cursor::new(&mut arena[..6])).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::BufferTooSmall);
+        assert_eq!(Raw::decode_from_buf(std::io::Cursor::new(&arena[..])).unwrap(), raw);
     }
 }
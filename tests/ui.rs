@@ -0,0 +1,13 @@
+#[cfg(feature = "prost-codec")]
+#[test]
+fn ui() {
+    let t = trybuild::TestCases::new();
+    t.compile_fail("tests/ui/*.rs");
+}
+
+#[cfg(all(feature = "protobuf3-codec", not(feature = "prost-codec")))]
+#[test]
+fn ui_protobuf3() {
+    let t = trybuild::TestCases::new();
+    t.compile_fail("tests/ui/protobuf3/*.rs");
+}
@@ -0,0 +1,8 @@
+#[derive(jinkela::Classicalize, Default)]
+#[jinkela(crate = "::jinkela::")]
+struct A {
+    #[prost(uint64)]
+    a: u64,
+}
+
+fn main() {}
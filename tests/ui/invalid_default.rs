@@ -0,0 +1,7 @@
+#[derive(jinkela::Classicalize, Default)]
+struct A {
+    #[prost(int32, optional, default = "five")]
+    count: Option<i32>,
+}
+
+fn main() {}
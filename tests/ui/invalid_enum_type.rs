@@ -0,0 +1,7 @@
+#[derive(jinkela::Classicalize, Default)]
+struct A {
+    #[prost(enumeration = "E<>>")]
+    e: i32,
+}
+
+fn main() {}
@@ -0,0 +1,7 @@
+#[derive(jinkela::Classicalize, Default)]
+struct A {
+    #[prost(map = 1)]
+    values: std::collections::HashMap<String, String>,
+}
+
+fn main() {}
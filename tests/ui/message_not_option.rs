@@ -0,0 +1,10 @@
+#[derive(Default)]
+struct B {}
+
+#[derive(jinkela::Classicalize, Default)]
+struct A {
+    #[prost(message, optional)]
+    b: B,
+}
+
+fn main() {}
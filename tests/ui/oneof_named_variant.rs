@@ -0,0 +1,7 @@
+#[derive(jinkela::Classicalize)]
+enum Kind {
+    #[prost(string, tag = "1")]
+    Name { name: String },
+}
+
+fn main() {}
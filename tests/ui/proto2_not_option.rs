@@ -0,0 +1,7 @@
+#[derive(jinkela::Classicalize, Default)]
+struct A {
+    #[prost(string, optional)]
+    name: String,
+}
+
+fn main() {}
@@ -0,0 +1,4 @@
+#[derive(jinkela::Classicalize)]
+struct Foo {}
+
+fn main() {}
@@ -0,0 +1,7 @@
+#[derive(jinkela::Classicalize, Default)]
+struct A {
+    #[prost(uint64, repeated)]
+    ids: std::collections::VecDeque<u64>,
+}
+
+fn main() {}
@@ -0,0 +1,7 @@
+#[derive(jinkela::Classicalize)]
+union U {
+    a: u32,
+    b: f32,
+}
+
+fn main() {}
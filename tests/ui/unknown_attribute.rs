@@ -0,0 +1,15 @@
+#[derive(jinkela::Classicalize, Default)]
+#[jinkela(unknown_enum = "ignore")]
+struct A {
+    #[prost(uint64)]
+    id: u64,
+}
+
+#[derive(jinkela::Classicalize, Default)]
+#[jinkela(lenient)]
+struct B {
+    #[prost(uint64)]
+    id: u64,
+}
+
+fn main() {}